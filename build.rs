@@ -0,0 +1,11 @@
+use std::env;
+
+/// Names the C API entrypoint after `DUCKDB_EXTENSION_NAME`, which the Makefile
+/// sets, falling back to the package name like `duckdb_entrypoint_c_api` does.
+fn main() {
+    println!("cargo:rerun-if-env-changed=DUCKDB_EXTENSION_NAME");
+    let extension_name = env::var("DUCKDB_EXTENSION_NAME")
+        .or_else(|_| env::var("CARGO_PKG_NAME"))
+        .expect("CARGO_PKG_NAME is set by cargo");
+    println!("cargo:rustc-env=EXTENSION_ENTRYPOINT={extension_name}_init_c_api");
+}
@@ -0,0 +1,616 @@
+use std::error::Error;
+use std::ffi::{c_void, CString};
+use std::ptr;
+use std::sync::Arc;
+
+use duckdb::core::{FlatVector, ListVector};
+use duckdb::ffi;
+use duckdb::ffi::duckdb_string_t;
+use duckdb::types::DuckString;
+
+use super::candidates::{MinHashCandidates, MinHashCandidatesCapped};
+use super::cluster::MinHashCluster;
+use super::dedup::MinHashDedup;
+use super::hll::HyperLogLog;
+use super::join::{MinHashJoin, MinHashSelfJoin};
+use super::minihasher::MinHasher;
+use super::shingleset::ShingleSet;
+use super::topk::MinHashTopK;
+use super::{
+    merge_signatures, read_list_vector, validate_positive, validate_seed, write_lists_at,
+    FamilyKey, HasherCache,
+};
+
+/// The running signature of one group: `band_count` bands of `band_size`
+/// minima each, over every token seen so far.
+pub struct Signature {
+    key: FamilyKey,
+    hashers: Arc<Vec<MinHasher>>,
+    minima: Vec<u64>,
+}
+
+impl Signature {
+    fn new(key: FamilyKey, hashers: Arc<Vec<MinHasher>>) -> Self {
+        let (band_count, band_size, _) = key;
+        Self {
+            key,
+            hashers,
+            minima: vec![u64::MAX; band_count * band_size],
+        }
+    }
+
+    fn add_token(&mut self, token: &str) {
+        let shingle_set = ShingleSet::from_tokens(&[token], 0, None);
+        let (_, band_size, _) = self.key;
+        for (hasher, minima) in self.hashers.iter().zip(self.minima.chunks_mut(band_size)) {
+            hasher.update_minima(minima, &shingle_set);
+        }
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), Box<dyn Error>> {
+        check_same_family(self.key, other.key)?;
+        merge_signatures(&mut self.minima, &other.minima)
+    }
+}
+
+/// Parameters are `BIGINT`s, since integer literals are not cast to `UBIGINT`
+/// when binding aggregates.
+pub fn validate_count(value: i64, param_name: &str) -> Result<usize, Box<dyn Error>> {
+    validate_positive(value.max(0) as usize, param_name)
+}
+
+pub fn check_same_family<T: PartialEq>(a: T, b: T) -> Result<(), Box<dyn Error>> {
+    if a != b {
+        return Err("parameters must be the same for every row of a group".into());
+    }
+    Ok(())
+}
+
+/// An aggregate function over groups of rows. DuckDB allocates room for an
+/// `Option<Box<State>>` per group; groups that never see a (non-NULL) row keep
+/// `None` and finalize to NULL.
+pub trait Aggregate {
+    type State;
+    const NAME: &'static str;
+
+    /// Creates the parameter types; they are destroyed once registered.
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type>;
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type;
+
+    /// Folds every row of `input` into the state of its group.
+    unsafe fn update(
+        cache: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Folds `source` into `target`; `source` is destroyed afterwards.
+    fn merge(target: &mut Self::State, source: Self::State) -> Result<(), Box<dyn Error>>;
+
+    /// Writes the result of every group to `result`, starting at row `offset`.
+    unsafe fn finalize(states: &[Option<&Self::State>], result: ffi::duckdb_vector, offset: usize);
+}
+
+/// An aggregate over `(token VARCHAR, parameters...)` that builds a
+/// [`Signature`] of the tokens in each group.
+trait TokenAggregate {
+    const NAME: &'static str;
+    const PARAMETERS: &'static [ffi::DUCKDB_TYPE];
+
+    /// The family parameters of one row, read from the parameter columns.
+    fn family(params: &[i64]) -> Result<FamilyKey, Box<dyn Error>>;
+
+    fn finalize(signature: &Signature) -> Vec<u64>;
+}
+
+/// `minhash_agg(token, num_perm, seed)`: the raw signature of the token set,
+/// comparable with `minhash_similarity`.
+struct MinHashAgg;
+
+impl TokenAggregate for MinHashAgg {
+    const NAME: &'static str = "minhash_agg";
+    const PARAMETERS: &'static [ffi::DUCKDB_TYPE] = &[
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+    ];
+
+    fn family(params: &[i64]) -> Result<FamilyKey, Box<dyn Error>> {
+        let num_perm = validate_count(params[0], "num_perm")?;
+        // A signature is a single hasher with `num_perm` seeds, i.e. a one-band family.
+        Ok((1, num_perm, validate_seed(params[1])?))
+    }
+
+    fn finalize(signature: &Signature) -> Vec<u64> {
+        signature.minima.clone()
+    }
+}
+
+/// `minhash_agg_bands(token, band_count, band_size, seed)`: band hashes of
+/// the token set, in the same format as `minhash`.
+struct MinHashAggBands;
+
+impl TokenAggregate for MinHashAggBands {
+    const NAME: &'static str = "minhash_agg_bands";
+    const PARAMETERS: &'static [ffi::DUCKDB_TYPE] = &[
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+    ];
+
+    fn family(params: &[i64]) -> Result<FamilyKey, Box<dyn Error>> {
+        let band_count = validate_count(params[0], "band_count")?;
+        let band_size = validate_count(params[1], "band_size")?;
+        Ok((band_count, band_size, validate_seed(params[2])?))
+    }
+
+    fn finalize(signature: &Signature) -> Vec<u64> {
+        let (_, band_size, _) = signature.key;
+        signature
+            .minima
+            .chunks(band_size)
+            .map(|minima| MinHasher::hash_minima(minima.iter().copied()))
+            .collect()
+    }
+}
+
+pub unsafe fn list_type() -> ffi::duckdb_logical_type {
+    let mut hash_type = ffi::duckdb_create_logical_type(ffi::DUCKDB_TYPE_DUCKDB_TYPE_UBIGINT);
+    let list_type = ffi::duckdb_create_list_type(hash_type);
+    ffi::duckdb_destroy_logical_type(&mut hash_type);
+    list_type
+}
+
+/// Writes one list of hashes per group, NULL for groups without a state.
+unsafe fn finalize_lists<S>(
+    states: &[Option<&S>],
+    mut result: ffi::duckdb_vector,
+    offset: usize,
+    finalize: impl Fn(&S) -> Vec<u64>,
+) {
+    let lists: Vec<Option<Vec<u64>>> = states.iter().map(|state| state.map(&finalize)).collect();
+    write_lists_at(&mut result, &lists, offset);
+}
+
+/// A row of the `LIST<STRUCT>` some aggregates return.
+pub trait StructRow {
+    /// The names and types of the struct fields.
+    const FIELDS: &'static [(&'static str, ffi::DUCKDB_TYPE)];
+
+    /// Writes the fields of this row at `idx` of the field vectors.
+    fn write(&self, fields: &mut [FlatVector], idx: usize);
+}
+
+pub unsafe fn struct_list_type<R: StructRow>() -> ffi::duckdb_logical_type {
+    let mut field_types: Vec<ffi::duckdb_logical_type> = R::FIELDS
+        .iter()
+        .map(|&(_, field_type)| ffi::duckdb_create_logical_type(field_type))
+        .collect();
+    let names: Vec<CString> = R::FIELDS
+        .iter()
+        .map(|&(name, _)| CString::new(name).expect("field names have no NUL bytes"))
+        .collect();
+    let mut name_ptrs: Vec<*const std::ffi::c_char> =
+        names.iter().map(|name| name.as_ptr()).collect();
+    let mut struct_type = ffi::duckdb_create_struct_type(
+        field_types.as_mut_ptr(),
+        name_ptrs.as_mut_ptr(),
+        R::FIELDS.len() as ffi::idx_t,
+    );
+    for field_type in &mut field_types {
+        ffi::duckdb_destroy_logical_type(field_type);
+    }
+    let list_type = ffi::duckdb_create_list_type(struct_type);
+    ffi::duckdb_destroy_logical_type(&mut struct_type);
+    list_type
+}
+
+/// Writes one list of rows per group, NULL for groups without a state.
+pub unsafe fn finalize_struct_lists<S, R: StructRow>(
+    states: &[Option<&S>],
+    result: ffi::duckdb_vector,
+    offset: usize,
+    finalize: impl Fn(&S) -> Vec<R>,
+) {
+    let lists: Vec<Option<Vec<R>>> = states.iter().map(|state| state.map(&finalize)).collect();
+    let mut output_lists = ListVector::from(result);
+    let mut child_offset = output_lists.len();
+    let total_len = child_offset + lists.iter().flatten().map(Vec::len).sum::<usize>();
+    let child = output_lists.struct_child(total_len);
+    let mut fields: Vec<FlatVector> = (0..R::FIELDS.len())
+        .map(|field_idx| child.child(field_idx, total_len))
+        .collect();
+    for (row_idx, list) in lists.iter().enumerate() {
+        match list {
+            Some(list) => {
+                for (idx, row) in list.iter().enumerate() {
+                    row.write(&mut fields, child_offset + idx);
+                }
+                output_lists.set_entry(offset + row_idx, child_offset, list.len());
+                child_offset += list.len();
+            }
+            None => {
+                output_lists.set_entry(offset + row_idx, child_offset, 0);
+                output_lists.set_null(offset + row_idx);
+            }
+        }
+    }
+    output_lists.set_len(total_len);
+}
+
+impl<A: TokenAggregate> Aggregate for A {
+    type State = Signature;
+    const NAME: &'static str = A::NAME;
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        std::iter::once(ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR)
+            .chain(A::PARAMETERS.iter().copied())
+            .map(|parameter| ffi::duckdb_create_logical_type(parameter))
+            .collect()
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        list_type()
+    }
+
+    unsafe fn update(
+        cache: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        let len = states.len();
+        let columns: Vec<FlatVector> = (0..=A::PARAMETERS.len())
+            .map(|col_idx| {
+                FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, col_idx as u64))
+            })
+            .collect();
+        let tokens = columns[0].as_slice_with_len::<duckdb_string_t>(len);
+        let params: Vec<&[i64]> = columns[1..]
+            .iter()
+            .map(|vector| vector.as_slice_with_len::<i64>(len))
+            .collect();
+
+        let mut row_params = vec![0; params.len()];
+        for row_idx in 0..len {
+            if columns
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                continue;
+            }
+            for (param, values) in row_params.iter_mut().zip(&params) {
+                *param = values[row_idx];
+            }
+            let key = A::family(&row_params)?;
+            let signature = state_mut::<Signature>(states[row_idx]).get_or_insert_with(|| {
+                let (band_count, band_size, seed) = key;
+                Box::new(Signature::new(key, cache.get(band_count, band_size, seed)))
+            });
+            check_same_family(signature.key, key)?;
+            let token = DuckString::new(&mut { tokens[row_idx] })
+                .as_str()
+                .to_string();
+            signature.add_token(&token);
+        }
+        Ok(())
+    }
+
+    fn merge(target: &mut Signature, source: Signature) -> Result<(), Box<dyn Error>> {
+        target.merge(&source)
+    }
+
+    unsafe fn finalize(states: &[Option<&Signature>], result: ffi::duckdb_vector, offset: usize) {
+        finalize_lists(states, result, offset, A::finalize);
+    }
+}
+
+/// `minhash_union(signature)`: the signature of the union of the sets, i.e.
+/// the elementwise minimum of the signatures in a group.
+struct MinHashUnion;
+
+impl Aggregate for MinHashUnion {
+    type State = Vec<u64>;
+    const NAME: &'static str = "minhash_union";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        vec![list_type()]
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        list_type()
+    }
+
+    unsafe fn update(
+        _: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        let signatures = read_list_vector::<u64>(
+            ffi::duckdb_data_chunk_get_vector(input, 0),
+            states.len(),
+            "signature",
+        )?;
+        for (&state, signature) in states.iter().zip(signatures) {
+            let Some(signature) = signature else {
+                continue;
+            };
+            match state_mut::<Vec<u64>>(state) {
+                Some(minima) => merge_signatures(minima, signature)?,
+                state => *state = Some(Box::new(signature.to_vec())),
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(target: &mut Vec<u64>, source: Vec<u64>) -> Result<(), Box<dyn Error>> {
+        merge_signatures(target, &source)
+    }
+
+    unsafe fn finalize(states: &[Option<&Vec<u64>>], result: ffi::duckdb_vector, offset: usize) {
+        finalize_lists(states, result, offset, Vec::clone);
+    }
+}
+
+/// The distinct shingles of a group, cut with the same `ngram_width` on every
+/// row.
+struct ShingleSketch {
+    ngram_width: usize,
+    hll: HyperLogLog,
+}
+
+/// `approx_shingle_count(text, ngram_width)`: the estimated number of distinct
+/// character shingles over all texts of a group, as `ShingleSet::new` cuts them.
+struct ApproxShingleCount;
+
+impl Aggregate for ApproxShingleCount {
+    type State = ShingleSketch;
+    const NAME: &'static str = "approx_shingle_count";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        vec![
+            ffi::duckdb_create_logical_type(ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR),
+            ffi::duckdb_create_logical_type(ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ]
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        ffi::duckdb_create_logical_type(ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT)
+    }
+
+    unsafe fn update(
+        _: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        let len = states.len();
+        let input_strings = FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, 0));
+        let input_ngram_width = FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, 1));
+        let strings = input_strings.as_slice_with_len::<duckdb_string_t>(len);
+        let ngram_widths = input_ngram_width.as_slice_with_len::<i64>(len);
+
+        for row_idx in 0..len {
+            if input_strings.row_is_null(row_idx as u64)
+                || input_ngram_width.row_is_null(row_idx as u64)
+            {
+                continue;
+            }
+            let ngram_width = validate_count(ngram_widths[row_idx], "ngram_width")?;
+            let sketch = state_mut::<ShingleSketch>(states[row_idx]).get_or_insert_with(|| {
+                Box::new(ShingleSketch {
+                    ngram_width,
+                    hll: HyperLogLog::default(),
+                })
+            });
+            check_same_family(sketch.ngram_width, ngram_width)?;
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            for &shingle in &ShingleSet::new(&string, ngram_width, row_idx, None).shingles {
+                sketch.hll.insert(shingle);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(target: &mut ShingleSketch, source: ShingleSketch) -> Result<(), Box<dyn Error>> {
+        check_same_family(target.ngram_width, source.ngram_width)?;
+        target.hll.merge(&source.hll);
+        Ok(())
+    }
+
+    unsafe fn finalize(
+        states: &[Option<&ShingleSketch>],
+        result: ffi::duckdb_vector,
+        offset: usize,
+    ) {
+        let mut counts = FlatVector::from(result);
+        for (row_idx, state) in states.iter().enumerate() {
+            match state {
+                Some(sketch) => {
+                    counts.as_mut_slice_with_len::<i64>(offset + states.len())[offset + row_idx] =
+                        sketch.hll.estimate().round() as i64
+                }
+                None => counts.set_null(offset + row_idx),
+            }
+        }
+    }
+}
+
+unsafe fn set_error(info: ffi::duckdb_function_info, error: Box<dyn Error>) {
+    if let Ok(message) = CString::new(error.to_string()) {
+        ffi::duckdb_aggregate_function_set_error(info, message.as_ptr());
+    }
+}
+
+pub unsafe fn state_mut<'a, S>(state: ffi::duckdb_aggregate_state) -> &'a mut Option<Box<S>> {
+    &mut *(state as *mut Option<Box<S>>)
+}
+
+unsafe extern "C" fn state_size<A: Aggregate>(_: ffi::duckdb_function_info) -> ffi::idx_t {
+    std::mem::size_of::<Option<Box<A::State>>>() as ffi::idx_t
+}
+
+unsafe extern "C" fn state_init<A: Aggregate>(
+    _: ffi::duckdb_function_info,
+    state: ffi::duckdb_aggregate_state,
+) {
+    ptr::write(state as *mut Option<Box<A::State>>, None);
+}
+
+unsafe extern "C" fn state_destroy<A: Aggregate>(
+    states: *mut ffi::duckdb_aggregate_state,
+    count: ffi::idx_t,
+) {
+    for &state in std::slice::from_raw_parts(states, count as usize) {
+        state_mut::<A::State>(state).take();
+    }
+}
+
+unsafe extern "C" fn state_update<A: Aggregate>(
+    info: ffi::duckdb_function_info,
+    input: ffi::duckdb_data_chunk,
+    states: *mut ffi::duckdb_aggregate_state,
+) {
+    let cache = &*(ffi::duckdb_aggregate_function_get_extra_info(info) as *const HasherCache);
+    let len = ffi::duckdb_data_chunk_get_size(input) as usize;
+    let states = std::slice::from_raw_parts(states, len);
+    if let Err(error) = A::update(cache, input, states) {
+        set_error(info, error);
+    }
+}
+
+unsafe fn combine<A: Aggregate>(
+    source: *mut ffi::duckdb_aggregate_state,
+    target: *mut ffi::duckdb_aggregate_state,
+    count: ffi::idx_t,
+) -> Result<(), Box<dyn Error>> {
+    let sources = std::slice::from_raw_parts(source, count as usize);
+    let targets = std::slice::from_raw_parts(target, count as usize);
+    for (&source, &target) in sources.iter().zip(targets) {
+        let Some(source) = state_mut::<A::State>(source).take() else {
+            continue;
+        };
+        match state_mut::<A::State>(target) {
+            Some(target) => A::merge(target, *source)?,
+            target => *target = Some(source),
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn state_combine<A: Aggregate>(
+    info: ffi::duckdb_function_info,
+    source: *mut ffi::duckdb_aggregate_state,
+    target: *mut ffi::duckdb_aggregate_state,
+    count: ffi::idx_t,
+) {
+    if let Err(error) = combine::<A>(source, target, count) {
+        set_error(info, error);
+    }
+}
+
+unsafe extern "C" fn state_finalize<A: Aggregate>(
+    _: ffi::duckdb_function_info,
+    source: *mut ffi::duckdb_aggregate_state,
+    result: ffi::duckdb_vector,
+    count: ffi::idx_t,
+    offset: ffi::idx_t,
+) {
+    let states: Vec<Option<&A::State>> = std::slice::from_raw_parts(source, count as usize)
+        .iter()
+        .map(|&state| state_mut::<A::State>(state).as_deref())
+        .collect();
+    A::finalize(&states, result, offset as usize);
+}
+
+unsafe extern "C" fn destroy_cache(cache: *mut c_void) {
+    drop(Box::from_raw(cache as *mut HasherCache));
+}
+
+/// Creates the function of `A`; the caller destroys it once registered.
+unsafe fn create_aggregate<A: Aggregate>() -> Result<ffi::duckdb_aggregate_function, Box<dyn Error>>
+{
+    let name = CString::new(A::NAME)?;
+    let function = ffi::duckdb_create_aggregate_function();
+    ffi::duckdb_aggregate_function_set_name(function, name.as_ptr());
+
+    for mut parameter in A::parameters() {
+        ffi::duckdb_aggregate_function_add_parameter(function, parameter);
+        ffi::duckdb_destroy_logical_type(&mut parameter);
+    }
+    let mut return_type = A::return_type();
+    ffi::duckdb_aggregate_function_set_return_type(function, return_type);
+    ffi::duckdb_destroy_logical_type(&mut return_type);
+
+    ffi::duckdb_aggregate_function_set_functions(
+        function,
+        Some(state_size::<A>),
+        Some(state_init::<A>),
+        Some(state_update::<A>),
+        Some(state_combine::<A>),
+        Some(state_finalize::<A>),
+    );
+    ffi::duckdb_aggregate_function_set_destructor(function, Some(state_destroy::<A>));
+    // NULL rows are handed to `update`, which skips them.
+    ffi::duckdb_aggregate_function_set_special_handling(function);
+    ffi::duckdb_aggregate_function_set_extra_info(
+        function,
+        Box::into_raw(Box::<HasherCache>::default()) as *mut c_void,
+        Some(destroy_cache),
+    );
+    Ok(function)
+}
+
+unsafe fn register_aggregate<A: Aggregate>(
+    con: ffi::duckdb_connection,
+) -> Result<(), Box<dyn Error>> {
+    let mut function = create_aggregate::<A>()?;
+    let state = ffi::duckdb_register_aggregate_function(con, function);
+    ffi::duckdb_destroy_aggregate_function(&mut function);
+    if state != ffi::DuckDBSuccess {
+        return Err(format!("Failed to register {} function", A::NAME).into());
+    }
+    Ok(())
+}
+
+/// Registers the overloads `A` and `B` of one aggregate under the name of `A`.
+unsafe fn register_aggregate_pair<A: Aggregate, B: Aggregate>(
+    con: ffi::duckdb_connection,
+) -> Result<(), Box<dyn Error>> {
+    let name = CString::new(A::NAME)?;
+    let mut set = ffi::duckdb_create_aggregate_function_set(name.as_ptr());
+    let mut added = true;
+    for mut function in [create_aggregate::<A>()?, create_aggregate::<B>()?] {
+        added &= ffi::duckdb_add_aggregate_function_to_set(set, function) == ffi::DuckDBSuccess;
+        ffi::duckdb_destroy_aggregate_function(&mut function);
+    }
+    let state = if added {
+        ffi::duckdb_register_aggregate_function_set(con, set)
+    } else {
+        ffi::DuckDBError
+    };
+    ffi::duckdb_destroy_aggregate_function_set(&mut set);
+    if state != ffi::DuckDBSuccess {
+        return Err(format!("Failed to register {} function", A::NAME).into());
+    }
+    Ok(())
+}
+
+/// Registers the aggregate functions, which duckdb-rs does not wrap yet.
+pub unsafe fn register(db: ffi::duckdb_database) -> Result<(), Box<dyn Error>> {
+    let mut con: ffi::duckdb_connection = ptr::null_mut();
+    if ffi::duckdb_connect(db, &mut con) != ffi::DuckDBSuccess {
+        return Err("Failed to connect to the database".into());
+    }
+    let result = register_aggregate_pair::<MinHashJoin, MinHashSelfJoin>(con)
+        .and_then(|_| register_aggregate::<MinHashAgg>(con))
+        .and_then(|_| register_aggregate::<MinHashAggBands>(con))
+        .and_then(|_| register_aggregate::<MinHashUnion>(con))
+        .and_then(|_| register_aggregate::<ApproxShingleCount>(con))
+        .and_then(|_| register_aggregate::<MinHashTopK>(con))
+        .and_then(|_| register_aggregate::<MinHashCluster>(con))
+        .and_then(|_| register_aggregate::<MinHashDedup>(con))
+        .and_then(|_| register_aggregate_pair::<MinHashCandidates, MinHashCandidatesCapped>(con));
+    ffi::duckdb_disconnect(&mut con);
+    result
+}
@@ -0,0 +1,55 @@
+use super::minihasher::mix;
+
+/// Checks that `bits` is a supported slot width, i.e. divides a byte.
+pub fn validate_bits(bits: usize) -> Result<u32, String> {
+    match bits {
+        1 | 2 | 4 | 8 => Ok(bits as u32),
+        _ => Err(format!("b must be 1, 2, 4 or 8, got {}", bits)),
+    }
+}
+
+/// b-bit MinHash (Li and König, 2010): packs only the lowest `bits` bits of
+/// every signature slot, lowest slot in the lowest bits. The minima are remixed
+/// first, as their own low bits are poorly mixed.
+/// `signature.len() * bits` must be a multiple of 8.
+pub fn pack(signature: &[u64], bits: u32) -> Vec<u8> {
+    let slots_per_byte = (8 / bits) as usize;
+    let mask = (1u64 << bits) - 1;
+    signature
+        .chunks(slots_per_byte)
+        .map(|slots| {
+            slots
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (slot_idx, &slot)| {
+                    byte | (((mix(slot) & mask) as u8) << (slot_idx as u32 * bits))
+                })
+        })
+        .collect()
+}
+
+/// Estimated Jaccard similarity of two packed signatures of the same length.
+///
+/// Equal minima always agree on their low bits, and unequal ones still do with
+/// probability about `C = 2^-bits`. The fraction of matching slots is thus
+/// `P = C + (1 - C) J`, and `J` is estimated as `(P - C) / (1 - C)`, clamped
+/// to `[0, 1]`.
+pub fn similarity(a: &[u8], b: &[u8], bits: u32) -> f64 {
+    let slots_per_byte = 8 / bits;
+    let mask = ((1u16 << bits) - 1) as u8;
+    let matches: u32 = a
+        .iter()
+        .zip(b)
+        .map(|(&byte_a, &byte_b)| {
+            (0..slots_per_byte)
+                .filter(|slot_idx| {
+                    let shift = slot_idx * bits;
+                    (byte_a >> shift) & mask == (byte_b >> shift) & mask
+                })
+                .count() as u32
+        })
+        .sum();
+    let matching_fraction = matches as f64 / (a.len() as u32 * slots_per_byte) as f64;
+    let chance = 1.0 / (1u32 << bits) as f64;
+    ((matching_fraction - chance) / (1.0 - chance)).clamp(0.0, 1.0)
+}
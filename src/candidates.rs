@@ -0,0 +1,215 @@
+use std::error::Error;
+
+use duckdb::core::FlatVector;
+use duckdb::ffi;
+use rustc_hash::FxHashMap;
+
+use super::aggregate::{
+    check_same_family, finalize_struct_lists, list_type, state_mut, struct_list_type,
+    validate_count, Aggregate, StructRow,
+};
+use super::{read_list_vector, HasherCache};
+
+/// The bucket size above which buckets are skipped unless a limit is given.
+pub const DEFAULT_MAX_BUCKET_SIZE: usize = 1000;
+
+/// The stored band hash lists of one group, keyed by id. Rows repeating an id
+/// that was already seen are ignored.
+pub struct CandidatesState {
+    max_bucket_size: usize,
+    hashes: FxHashMap<i64, Vec<u64>>,
+}
+
+impl CandidatesState {
+    fn merge(&mut self, other: Self) -> Result<(), Box<dyn Error>> {
+        check_same_family(self.max_bucket_size, other.max_bucket_size)?;
+        for (id, hashes) in other.hashes {
+            self.hashes.entry(id).or_insert(hashes);
+        }
+        Ok(())
+    }
+
+    /// Every pair of ids sharing a `(band, hash)` bucket, once, with the number
+    /// of buckets they share, ordered by ids. Buckets with more than
+    /// `max_bucket_size` ids are skipped as a whole rather than truncated, so
+    /// their pairs are missing instead of depending on which ids came first.
+    fn pairs(&self) -> Vec<CandidatePair> {
+        let mut buckets: FxHashMap<(usize, u64), Vec<i64>> = FxHashMap::default();
+        for (&id, hashes) in &self.hashes {
+            for (band_idx, &hash) in hashes.iter().enumerate() {
+                buckets.entry((band_idx, hash)).or_default().push(id);
+            }
+        }
+
+        let mut shared: FxHashMap<(i64, i64), usize> = FxHashMap::default();
+        for mut ids in buckets.into_values() {
+            if ids.len() > self.max_bucket_size {
+                continue;
+            }
+            ids.sort_unstable();
+            for (i, &id_a) in ids.iter().enumerate() {
+                for &id_b in &ids[i + 1..] {
+                    *shared.entry((id_a, id_b)).or_default() += 1;
+                }
+            }
+        }
+        let mut pairs: Vec<CandidatePair> = shared
+            .into_iter()
+            .map(|((id_a, id_b), shared_bands)| CandidatePair {
+                id_a,
+                id_b,
+                shared_bands,
+            })
+            .collect();
+        pairs.sort_unstable_by_key(|pair| (pair.id_a, pair.id_b));
+        pairs
+    }
+}
+
+pub struct CandidatePair {
+    id_a: i64,
+    id_b: i64,
+    shared_bands: usize,
+}
+
+impl StructRow for CandidatePair {
+    const FIELDS: &'static [(&'static str, ffi::DUCKDB_TYPE)] = &[
+        ("id_a", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ("id_b", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ("shared_bands", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+    ];
+
+    fn write(&self, fields: &mut [FlatVector], idx: usize) {
+        fields[0].as_mut_slice::<i64>()[idx] = self.id_a;
+        fields[1].as_mut_slice::<i64>()[idx] = self.id_b;
+        fields[2].as_mut_slice::<i64>()[idx] = self.shared_bands as i64;
+    }
+}
+
+/// Folds the `(id, hashes)` rows of `input` into their groups, with the
+/// `max_bucket_size` column, if any, following them.
+unsafe fn update_candidates(
+    input: ffi::duckdb_data_chunk,
+    states: &[ffi::duckdb_aggregate_state],
+    has_max_bucket_size: bool,
+) -> Result<(), Box<dyn Error>> {
+    let len = states.len();
+    let input_ids = FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, 0));
+    let ids = input_ids.as_slice_with_len::<i64>(len);
+    let hash_lists =
+        read_list_vector::<u64>(ffi::duckdb_data_chunk_get_vector(input, 1), len, "hashes")?;
+    let input_max_bucket_size =
+        has_max_bucket_size.then(|| FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, 2)));
+
+    for (row_idx, hashes) in hash_lists.into_iter().enumerate() {
+        let max_bucket_size = match &input_max_bucket_size {
+            Some(vector) if vector.row_is_null(row_idx as u64) => continue,
+            Some(vector) => validate_count(
+                vector.as_slice_with_len::<i64>(len)[row_idx],
+                "max_bucket_size",
+            )?,
+            None => DEFAULT_MAX_BUCKET_SIZE,
+        };
+        let state = state_mut::<CandidatesState>(states[row_idx]).get_or_insert_with(|| {
+            Box::new(CandidatesState {
+                max_bucket_size,
+                hashes: FxHashMap::default(),
+            })
+        });
+        check_same_family(state.max_bucket_size, max_bucket_size)?;
+
+        let Some(hashes) = hashes else {
+            continue;
+        };
+        if input_ids.row_is_null(row_idx as u64) {
+            continue;
+        }
+        state
+            .hashes
+            .entry(ids[row_idx])
+            .or_insert_with(|| hashes.to_vec());
+    }
+    Ok(())
+}
+
+/// `minhash_candidates(id, hashes, max_bucket_size)`: candidate pairs from band
+/// hash lists computed earlier, e.g. by `minhash`. Buckets with more than
+/// `max_bucket_size` ids, typically from boilerplate strings, are skipped.
+pub struct MinHashCandidatesCapped;
+
+impl Aggregate for MinHashCandidatesCapped {
+    type State = CandidatesState;
+    const NAME: &'static str = "minhash_candidates";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        vec![
+            ffi::duckdb_create_logical_type(ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+            list_type(),
+            ffi::duckdb_create_logical_type(ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ]
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        struct_list_type::<CandidatePair>()
+    }
+
+    unsafe fn update(
+        _: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        update_candidates(input, states, true)
+    }
+
+    fn merge(target: &mut CandidatesState, source: CandidatesState) -> Result<(), Box<dyn Error>> {
+        target.merge(source)
+    }
+
+    unsafe fn finalize(
+        states: &[Option<&CandidatesState>],
+        result: ffi::duckdb_vector,
+        offset: usize,
+    ) {
+        finalize_struct_lists(states, result, offset, CandidatesState::pairs);
+    }
+}
+
+/// `minhash_candidates(id, hashes)`: candidate pairs with buckets capped at
+/// [`DEFAULT_MAX_BUCKET_SIZE`] ids.
+pub struct MinHashCandidates;
+
+impl Aggregate for MinHashCandidates {
+    type State = CandidatesState;
+    const NAME: &'static str = "minhash_candidates";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        vec![
+            ffi::duckdb_create_logical_type(ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+            list_type(),
+        ]
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        struct_list_type::<CandidatePair>()
+    }
+
+    unsafe fn update(
+        _: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        update_candidates(input, states, false)
+    }
+
+    fn merge(target: &mut CandidatesState, source: CandidatesState) -> Result<(), Box<dyn Error>> {
+        target.merge(source)
+    }
+
+    unsafe fn finalize(
+        states: &[Option<&CandidatesState>],
+        result: ffi::duckdb_vector,
+        offset: usize,
+    ) {
+        finalize_struct_lists(states, result, offset, CandidatesState::pairs);
+    }
+}
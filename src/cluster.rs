@@ -0,0 +1,339 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use duckdb::core::FlatVector;
+use duckdb::ffi;
+use duckdb::ffi::duckdb_string_t;
+use duckdb::types::DuckString;
+use rustc_hash::FxHashMap;
+
+use super::aggregate::{
+    check_same_family, finalize_struct_lists, state_mut, struct_list_type, validate_count,
+    Aggregate, StructRow,
+};
+use super::minihasher::MinHasher;
+use super::shingleset::ShingleSet;
+use super::{band_hashes, validate_seed, validate_unit_interval, FamilyKey, HasherCache};
+
+/// Union-find over row indices, with path halving.
+pub struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    pub fn find(&mut self, mut idx: usize) -> usize {
+        while self.parents[idx] != idx {
+            self.parents[idx] = self.parents[self.parents[idx]];
+            idx = self.parents[idx];
+        }
+        idx
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        self.parents[root_a.max(root_b)] = root_a.min(root_b);
+    }
+}
+
+/// The shingles of a row and their band hashes.
+pub struct HashedText {
+    shingle_set: ShingleSet,
+    band_hashes: Vec<u64>,
+}
+
+impl HashedText {
+    /// `None` for strings shorter than `ngram_width`, which have no shingles.
+    pub fn new(text: &str, ngram_width: usize, hashers: &[MinHasher]) -> Option<Self> {
+        let shingle_set = ShingleSet::new(text, ngram_width, 0, None);
+        if shingle_set.shingles.is_empty() {
+            return None;
+        }
+        Some(Self {
+            band_hashes: band_hashes(&shingle_set, hashers),
+            shingle_set,
+        })
+    }
+}
+
+/// Links the rows sharing a band. With a positive `threshold`, a pair of rows
+/// sharing a band is only linked when their exact Jaccard similarity reaches
+/// it; rows without shingles are never linked.
+///
+/// Verification skips the pairs of a bucket already in one component, so a
+/// bucket of `n` near-duplicates takes about `n` Jaccard computations, but a
+/// bucket of `n` mutually dissimilar rows, e.g. sharing a common prefix, takes
+/// `n * (n - 1) / 2`.
+pub fn link_components(rows: &[Option<HashedText>], threshold: f64) -> DisjointSet {
+    let mut buckets: FxHashMap<(usize, u64), Vec<usize>> = FxHashMap::default();
+    for (row_idx, row) in rows.iter().enumerate() {
+        let Some(row) = row else {
+            continue;
+        };
+        for (band_idx, &hash) in row.band_hashes.iter().enumerate() {
+            buckets.entry((band_idx, hash)).or_default().push(row_idx);
+        }
+    }
+
+    let mut components = DisjointSet::new(rows.len());
+    for members in buckets.values() {
+        if threshold <= 0.0 {
+            for &member in &members[1..] {
+                components.union(members[0], member);
+            }
+            continue;
+        }
+        for (i, &a) in members.iter().enumerate() {
+            for &b in &members[i + 1..] {
+                if components.find(a) == components.find(b) {
+                    continue;
+                }
+                if let (Some(row_a), Some(row_b)) = (&rows[a], &rows[b]) {
+                    if row_a.shingle_set.jaccard_similarity(&row_b.shingle_set) >= threshold {
+                        components.union(a, b);
+                    }
+                }
+            }
+        }
+    }
+    components
+}
+
+/// The parameters of a clustering, which must be the same for every row of a
+/// group.
+#[derive(Clone, Copy, PartialEq)]
+struct ClusterParams {
+    ngram_width: usize,
+    family: FamilyKey,
+    threshold: f64,
+}
+
+/// The hashed rows of one group with their ids and ranks. Rows without
+/// shingles are `None`.
+pub struct ClusterState {
+    params: ClusterParams,
+    hashers: Arc<Vec<MinHasher>>,
+    ids: Vec<i64>,
+    ranks: Vec<i64>,
+    rows: Vec<Option<HashedText>>,
+}
+
+/// The row of a component with the smallest `(rank, id)`, and the size of the
+/// component.
+struct Representative {
+    rank: i64,
+    id: i64,
+    size: usize,
+}
+
+impl ClusterState {
+    pub fn merge(&mut self, other: Self) -> Result<(), Box<dyn Error>> {
+        check_same_family(self.params, other.params)?;
+        self.ids.extend(other.ids);
+        self.ranks.extend(other.ranks);
+        self.rows.extend(other.rows);
+        Ok(())
+    }
+
+    /// The components of the rows, given as the root of every row, and their
+    /// representatives keyed by root.
+    fn components(&self) -> (Vec<usize>, FxHashMap<usize, Representative>) {
+        let mut components = link_components(&self.rows, self.params.threshold);
+        let roots: Vec<usize> = (0..self.rows.len())
+            .map(|row_idx| components.find(row_idx))
+            .collect();
+        let mut representatives: FxHashMap<usize, Representative> = FxHashMap::default();
+        for ((&root, &id), &rank) in roots.iter().zip(&self.ids).zip(&self.ranks) {
+            let representative =
+                representatives
+                    .entry(root)
+                    .or_insert(Representative { rank, id, size: 0 });
+            if (rank, id) < (representative.rank, representative.id) {
+                (representative.rank, representative.id) = (rank, id);
+            }
+            representative.size += 1;
+        }
+        (roots, representatives)
+    }
+
+    /// Every row with the id of the representative of its component, ordered
+    /// by ids.
+    fn clusters(&self) -> Vec<ClusterRow> {
+        let (roots, representatives) = self.components();
+        let mut clusters: Vec<ClusterRow> = self
+            .ids
+            .iter()
+            .zip(&roots)
+            .map(|(&id, root)| ClusterRow {
+                id,
+                cluster_id: representatives[root].id,
+            })
+            .collect();
+        clusters.sort_unstable_by_key(|row| (row.id, row.cluster_id));
+        clusters
+    }
+
+    /// The id of the representative of every component, with the size of the
+    /// component, ordered by ids.
+    pub fn representatives(&self) -> Vec<(i64, usize)> {
+        let (_, representatives) = self.components();
+        let mut kept: Vec<(i64, usize)> = representatives
+            .into_values()
+            .map(|representative| (representative.id, representative.size))
+            .collect();
+        kept.sort_unstable();
+        kept
+    }
+}
+
+pub struct ClusterRow {
+    id: i64,
+    cluster_id: i64,
+}
+
+impl StructRow for ClusterRow {
+    const FIELDS: &'static [(&'static str, ffi::DUCKDB_TYPE)] = &[
+        ("id", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ("cluster_id", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+    ];
+
+    fn write(&self, fields: &mut [FlatVector], idx: usize) {
+        fields[0].as_mut_slice::<i64>()[idx] = self.id;
+        fields[1].as_mut_slice::<i64>()[idx] = self.cluster_id;
+    }
+}
+
+/// Folds the `(id, text)` rows of `input` into their groups. The parameter
+/// columns follow them: `threshold` at `threshold_col`, the rank of the row at
+/// `rank_col`, if any, and `ngram_width`, `band_count`, `band_size` and `seed`
+/// in that order around them. Rows without a rank are ranked by their id, and
+/// rows with a NULL rank last.
+pub unsafe fn update_clusters(
+    cache: &HasherCache,
+    input: ffi::duckdb_data_chunk,
+    states: &[ffi::duckdb_aggregate_state],
+    threshold_col: usize,
+    rank_col: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let len = states.len();
+    let column_count = ffi::duckdb_data_chunk_get_column_count(input) as usize;
+    let columns: Vec<FlatVector> = (0..column_count)
+        .map(|col_idx| FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, col_idx as u64)))
+        .collect();
+    let ids = columns[0].as_slice_with_len::<i64>(len);
+    let texts = columns[1].as_slice_with_len::<duckdb_string_t>(len);
+    let param_cols: Vec<usize> = (2..column_count)
+        .filter(|&col_idx| Some(col_idx) != rank_col)
+        .collect();
+    let counts: Vec<&[i64]> = param_cols
+        .iter()
+        .filter(|&&col_idx| col_idx != threshold_col)
+        .map(|&col_idx| columns[col_idx].as_slice_with_len::<i64>(len))
+        .collect();
+    let thresholds = columns[threshold_col].as_slice_with_len::<f64>(len);
+
+    for row_idx in 0..len {
+        if param_cols
+            .iter()
+            .any(|&col_idx| columns[col_idx].row_is_null(row_idx as u64))
+        {
+            continue;
+        }
+        let row_params = ClusterParams {
+            ngram_width: validate_count(counts[0][row_idx], "ngram_width")?,
+            family: (
+                validate_count(counts[1][row_idx], "band_count")?,
+                validate_count(counts[2][row_idx], "band_size")?,
+                validate_seed(counts[3][row_idx])?,
+            ),
+            threshold: validate_unit_interval(thresholds[row_idx], "threshold")?,
+        };
+        let state = state_mut::<ClusterState>(states[row_idx]).get_or_insert_with(|| {
+            let (band_count, band_size, seed) = row_params.family;
+            Box::new(ClusterState {
+                params: row_params,
+                hashers: cache.get(band_count, band_size, seed),
+                ids: Vec::new(),
+                ranks: Vec::new(),
+                rows: Vec::new(),
+            })
+        });
+        check_same_family(state.params, row_params)?;
+
+        if columns[0].row_is_null(row_idx as u64) {
+            continue;
+        }
+        let row = if columns[1].row_is_null(row_idx as u64) {
+            None
+        } else {
+            let text = DuckString::new(&mut { texts[row_idx] })
+                .as_str()
+                .to_string();
+            HashedText::new(&text, row_params.ngram_width, &state.hashers)
+        };
+        let rank = match rank_col {
+            Some(col_idx) if columns[col_idx].row_is_null(row_idx as u64) => i64::MAX,
+            Some(col_idx) => columns[col_idx].as_slice_with_len::<i64>(len)[row_idx],
+            None => ids[row_idx],
+        };
+        state.ids.push(ids[row_idx]);
+        state.ranks.push(rank);
+        state.rows.push(row);
+    }
+    Ok(())
+}
+
+/// `minhash_cluster(id, text, ngram_width, band_count, band_size, seed,
+/// threshold)`: the connected components of the rows linked by
+/// [`link_components`]. Every row with a non-NULL id is assigned the smallest
+/// id of its component, so rows without shingles are clusters of their own.
+pub struct MinHashCluster;
+
+impl Aggregate for MinHashCluster {
+    type State = ClusterState;
+    const NAME: &'static str = "minhash_cluster";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        [
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_DOUBLE,
+        ]
+        .into_iter()
+        .map(|parameter| ffi::duckdb_create_logical_type(parameter))
+        .collect()
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        struct_list_type::<ClusterRow>()
+    }
+
+    unsafe fn update(
+        cache: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        update_clusters(cache, input, states, 6, None)
+    }
+
+    fn merge(target: &mut ClusterState, source: ClusterState) -> Result<(), Box<dyn Error>> {
+        target.merge(source)
+    }
+
+    unsafe fn finalize(
+        states: &[Option<&ClusterState>],
+        result: ffi::duckdb_vector,
+        offset: usize,
+    ) {
+        finalize_struct_lists(states, result, offset, ClusterState::clusters);
+    }
+}
@@ -0,0 +1,84 @@
+use std::error::Error;
+
+use duckdb::core::FlatVector;
+use duckdb::ffi;
+
+use super::aggregate::{finalize_struct_lists, struct_list_type, Aggregate, StructRow};
+use super::cluster::{update_clusters, ClusterState};
+use super::HasherCache;
+
+pub struct KeptRow {
+    id: i64,
+    cluster_size: usize,
+}
+
+impl StructRow for KeptRow {
+    const FIELDS: &'static [(&'static str, ffi::DUCKDB_TYPE)] = &[
+        ("id", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ("cluster_size", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+    ];
+
+    fn write(&self, fields: &mut [FlatVector], idx: usize) {
+        fields[0].as_mut_slice::<i64>()[idx] = self.id;
+        fields[1].as_mut_slice::<i64>()[idx] = self.cluster_size as i64;
+    }
+}
+
+/// `minhash_dedup(id, text, threshold, keep_rank, ngram_width, band_count,
+/// band_size, seed)`: drops near-duplicates, keeping one row per cluster of
+/// [`link_components`](super::cluster::link_components) as
+/// `(id, cluster_size)`. The kept row is the one with the smallest
+/// `keep_rank`, then the smallest id; rows with a NULL rank come last. Rows
+/// without shingles are always kept.
+pub struct MinHashDedup;
+
+impl Aggregate for MinHashDedup {
+    type State = ClusterState;
+    const NAME: &'static str = "minhash_dedup";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        [
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_DOUBLE,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ]
+        .into_iter()
+        .map(|parameter| ffi::duckdb_create_logical_type(parameter))
+        .collect()
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        struct_list_type::<KeptRow>()
+    }
+
+    unsafe fn update(
+        cache: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        update_clusters(cache, input, states, 2, Some(3))
+    }
+
+    fn merge(target: &mut ClusterState, source: ClusterState) -> Result<(), Box<dyn Error>> {
+        target.merge(source)
+    }
+
+    unsafe fn finalize(
+        states: &[Option<&ClusterState>],
+        result: ffi::duckdb_vector,
+        offset: usize,
+    ) {
+        finalize_struct_lists(states, result, offset, |state| {
+            state
+                .representatives()
+                .into_iter()
+                .map(|(id, cluster_size)| KeptRow { id, cluster_size })
+                .collect()
+        });
+    }
+}
@@ -0,0 +1,56 @@
+use super::minihasher::mix;
+
+/// Number of index bits; `2^PRECISION` registers give a relative standard
+/// error of about `1.04 / 2^(PRECISION / 2)`, i.e. 1.6%.
+const PRECISION: u32 = 12;
+const NUM_REGISTERS: usize = 1 << PRECISION;
+
+/// HyperLogLog sketch (Flajolet et al., 2007) of the distinct shingles of a
+/// corpus, with linear counting for small cardinalities.
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; NUM_REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn insert(&mut self, shingle: u32) {
+        let hash = mix(shingle as u64);
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // The rank of the first set bit after the index bits, capped by a
+        // sentinel bit so that it fits the remaining `64 - PRECISION` bits.
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (register, &other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(other);
+        }
+    }
+
+    pub fn estimate(&self) -> f64 {
+        let num_registers = NUM_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / num_registers);
+        let harmonic_sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-(rank as i32)))
+            .sum();
+        let estimate = alpha * num_registers * num_registers / harmonic_sum;
+
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if estimate <= 2.5 * num_registers && empty > 0 {
+            num_registers * (num_registers / empty as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
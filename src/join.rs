@@ -0,0 +1,281 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use duckdb::core::FlatVector;
+use duckdb::ffi;
+use duckdb::ffi::duckdb_string_t;
+use duckdb::types::DuckString;
+use rustc_hash::FxHashMap;
+
+use super::aggregate::{
+    check_same_family, finalize_struct_lists, state_mut, struct_list_type, validate_count,
+    Aggregate, StructRow,
+};
+use super::minihasher::MinHasher;
+use super::shingleset::ShingleSet;
+use super::{band_hashes, validate_seed, validate_unit_interval, FamilyKey, HasherCache};
+
+/// Similarity implied by sharing `shared` of `band_count` bands, inverting
+/// `P(band collision) = s^band_size`.
+pub fn estimate_similarity(shared: usize, band_count: usize, band_size: usize) -> f64 {
+    (shared as f64 / band_count as f64).powf(1.0 / band_size as f64)
+}
+
+/// The parameters of a join, which must be the same for every row of a group.
+#[derive(Clone, Copy, PartialEq)]
+struct JoinParams {
+    ngram_width: usize,
+    family: FamilyKey,
+    threshold: f64,
+}
+
+/// The band hashes of the rows of one group, keyed by id. Rows repeating an id
+/// that was already seen are ignored.
+pub struct JoinState {
+    params: JoinParams,
+    hashers: Arc<Vec<MinHasher>>,
+    left: FxHashMap<i64, Vec<u64>>,
+    right: FxHashMap<i64, Vec<u64>>,
+}
+
+impl JoinState {
+    fn merge(&mut self, other: Self) -> Result<(), Box<dyn Error>> {
+        check_same_family(self.params, other.params)?;
+        for (id, hashes) in other.left {
+            self.left.entry(id).or_insert(hashes);
+        }
+        for (id, hashes) in other.right {
+            self.right.entry(id).or_insert(hashes);
+        }
+        Ok(())
+    }
+
+    /// The pairs above the threshold, ordered by ids. A self-join probes the
+    /// left rows against themselves and reports each pair once.
+    fn pairs(&self, self_join: bool) -> Vec<JoinPair> {
+        let (band_count, band_size, _) = self.params.family;
+        let right = if self_join { &self.left } else { &self.right };
+        let mut buckets: FxHashMap<(usize, u64), Vec<i64>> = FxHashMap::default();
+        for (&right_id, hashes) in right {
+            for (band_idx, &hash) in hashes.iter().enumerate() {
+                buckets.entry((band_idx, hash)).or_default().push(right_id);
+            }
+        }
+
+        let mut pairs = Vec::new();
+        let mut shared: FxHashMap<i64, usize> = FxHashMap::default();
+        for (&left_id, hashes) in &self.left {
+            shared.clear();
+            for (band_idx, &hash) in hashes.iter().enumerate() {
+                for &right_id in buckets.get(&(band_idx, hash)).into_iter().flatten() {
+                    *shared.entry(right_id).or_default() += 1;
+                }
+            }
+            pairs.extend(
+                shared
+                    .iter()
+                    .filter(|&(&right_id, _)| !self_join || left_id < right_id)
+                    .map(|(&right_id, &count)| JoinPair {
+                        left_id,
+                        right_id,
+                        estimated_similarity: estimate_similarity(count, band_count, band_size),
+                    })
+                    .filter(|pair| pair.estimated_similarity >= self.params.threshold),
+            );
+        }
+        pairs.sort_unstable_by_key(|pair| (pair.left_id, pair.right_id));
+        pairs
+    }
+}
+
+pub struct JoinPair {
+    left_id: i64,
+    right_id: i64,
+    estimated_similarity: f64,
+}
+
+impl StructRow for JoinPair {
+    const FIELDS: &'static [(&'static str, ffi::DUCKDB_TYPE)] = &[
+        ("left_id", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ("right_id", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ("estimated_similarity", ffi::DUCKDB_TYPE_DUCKDB_TYPE_DOUBLE),
+    ];
+
+    fn write(&self, fields: &mut [FlatVector], idx: usize) {
+        fields[0].as_mut_slice::<i64>()[idx] = self.left_id;
+        fields[1].as_mut_slice::<i64>()[idx] = self.right_id;
+        fields[2].as_mut_slice::<f64>()[idx] = self.estimated_similarity;
+    }
+}
+
+/// Band hashes of a string, or `None` for strings shorter than `ngram_width`,
+/// which have no shingles and cannot be similar to anything.
+fn hash_text(text: &str, ngram_width: usize, hashers: &[MinHasher]) -> Option<Vec<u64>> {
+    let shingle_set = ShingleSet::new(text, ngram_width, 0, None);
+    if shingle_set.shingles.is_empty() {
+        return None;
+    }
+    Some(band_hashes(&shingle_set, hashers))
+}
+
+/// Folds the rows of `input` into their groups. `sides` are the `(id, text)`
+/// columns of the left and, for a two-sided join, the right rows, and the
+/// parameter columns follow them.
+unsafe fn update_join(
+    cache: &HasherCache,
+    input: ffi::duckdb_data_chunk,
+    states: &[ffi::duckdb_aggregate_state],
+    side_count: usize,
+) -> Result<(), Box<dyn Error>> {
+    let len = states.len();
+    let column_count = ffi::duckdb_data_chunk_get_column_count(input) as usize;
+    let columns: Vec<FlatVector> = (0..column_count)
+        .map(|col_idx| FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, col_idx as u64)))
+        .collect();
+    let (sides, params) = columns.split_at(2 * side_count);
+    let counts: Vec<&[i64]> = params[..4]
+        .iter()
+        .map(|vector| vector.as_slice_with_len::<i64>(len))
+        .collect();
+    let thresholds = params[4].as_slice_with_len::<f64>(len);
+
+    for row_idx in 0..len {
+        if params
+            .iter()
+            .any(|vector| vector.row_is_null(row_idx as u64))
+        {
+            continue;
+        }
+        let row_params = JoinParams {
+            ngram_width: validate_count(counts[0][row_idx], "ngram_width")?,
+            family: (
+                validate_count(counts[1][row_idx], "band_count")?,
+                validate_count(counts[2][row_idx], "band_size")?,
+                validate_seed(counts[3][row_idx])?,
+            ),
+            threshold: validate_unit_interval(thresholds[row_idx], "threshold")?,
+        };
+        let state = state_mut::<JoinState>(states[row_idx]).get_or_insert_with(|| {
+            let (band_count, band_size, seed) = row_params.family;
+            Box::new(JoinState {
+                params: row_params,
+                hashers: cache.get(band_count, band_size, seed),
+                left: FxHashMap::default(),
+                right: FxHashMap::default(),
+            })
+        });
+        check_same_family(state.params, row_params)?;
+
+        for (side_idx, side) in sides.chunks(2).enumerate() {
+            let (ids, texts) = (&side[0], &side[1]);
+            if ids.row_is_null(row_idx as u64) || texts.row_is_null(row_idx as u64) {
+                continue;
+            }
+            let id = ids.as_slice_with_len::<i64>(len)[row_idx];
+            let rows = if side_idx == 0 {
+                &mut state.left
+            } else {
+                &mut state.right
+            };
+            if rows.contains_key(&id) {
+                continue;
+            }
+            let text =
+                DuckString::new(&mut { texts.as_slice_with_len::<duckdb_string_t>(len)[row_idx] })
+                    .as_str()
+                    .to_string();
+            if let Some(hashes) = hash_text(&text, row_params.ngram_width, &state.hashers) {
+                rows.insert(id, hashes);
+            }
+        }
+    }
+    Ok(())
+}
+
+unsafe fn join_parameters(side_count: usize) -> Vec<ffi::duckdb_logical_type> {
+    let sides = [
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR,
+    ]
+    .repeat(side_count);
+    let params = [
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_DOUBLE,
+    ];
+    sides
+        .into_iter()
+        .chain(params)
+        .map(|parameter| ffi::duckdb_create_logical_type(parameter))
+        .collect()
+}
+
+/// `minhash_join(left_id, left_text, right_id, right_text, ngram_width,
+/// band_count, band_size, seed, threshold)`: the pairs of left and right rows
+/// whose estimated similarity is at least `threshold`.
+pub struct MinHashJoin;
+
+impl Aggregate for MinHashJoin {
+    type State = JoinState;
+    const NAME: &'static str = "minhash_join";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        join_parameters(2)
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        struct_list_type::<JoinPair>()
+    }
+
+    unsafe fn update(
+        cache: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        update_join(cache, input, states, 2)
+    }
+
+    fn merge(target: &mut JoinState, source: JoinState) -> Result<(), Box<dyn Error>> {
+        target.merge(source)
+    }
+
+    unsafe fn finalize(states: &[Option<&JoinState>], result: ffi::duckdb_vector, offset: usize) {
+        finalize_struct_lists(states, result, offset, |state| state.pairs(false));
+    }
+}
+
+/// `minhash_join(id, text, ngram_width, band_count, band_size, seed,
+/// threshold)`: the similar pairs among the rows of one input, each reported
+/// once with `left_id < right_id`.
+pub struct MinHashSelfJoin;
+
+impl Aggregate for MinHashSelfJoin {
+    type State = JoinState;
+    const NAME: &'static str = "minhash_join";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        join_parameters(1)
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        struct_list_type::<JoinPair>()
+    }
+
+    unsafe fn update(
+        cache: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        update_join(cache, input, states, 1)
+    }
+
+    fn merge(target: &mut JoinState, source: JoinState) -> Result<(), Box<dyn Error>> {
+        target.merge(source)
+    }
+
+    unsafe fn finalize(states: &[Option<&JoinState>], result: ffi::duckdb_vector, offset: usize) {
+        finalize_struct_lists(states, result, offset, |state| state.pairs(true));
+    }
+}
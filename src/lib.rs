@@ -1,4 +1,6 @@
 use std::error::Error;
+use std::ffi::CString;
+use std::sync::{Arc, PoisonError, RwLock};
 
 use rand::rngs::StdRng;
 use rand::SeedableRng;
@@ -7,34 +9,378 @@ use duckdb::ffi;
 use duckdb::ffi::duckdb_string_t;
 use duckdb::types::DuckString;
 use duckdb::{
-    core::{DataChunkHandle, LogicalTypeHandle, LogicalTypeId},
+    core::{DataChunkHandle, FlatVector, Inserter, LogicalTypeHandle, LogicalTypeId},
     vscalar::{ScalarFunctionSignature, VScalar},
-    vtab::arrow::WritableVector,
+    vtab::{arrow::WritableVector, BindInfo},
     Connection, Result,
 };
-use duckdb_loadable_macros::duckdb_entrypoint_c_api;
+use rustc_hash::FxHashMap;
 
+// Module paths are explicit, and modules refer to each other through
+// `super::`, so that src/wasm_lib.rs can include this file as a module.
+#[path = "aggregate.rs"]
+mod aggregate;
+#[path = "bbit.rs"]
+pub mod bbit;
+#[path = "candidates.rs"]
+mod candidates;
+#[path = "cluster.rs"]
+mod cluster;
+#[path = "dedup.rs"]
+mod dedup;
+#[path = "hll.rs"]
+pub mod hll;
+#[path = "join.rs"]
+mod join;
+#[path = "lsh.rs"]
+pub mod lsh;
+#[path = "minihasher.rs"]
 pub mod minihasher;
+#[path = "normalize.rs"]
+pub mod normalize;
+#[path = "oph.rs"]
+pub mod oph;
+#[path = "shingleset.rs"]
 pub mod shingleset;
+#[path = "simhash.rs"]
+pub mod simhash;
+#[path = "topk.rs"]
+mod topk;
+#[path = "tuning.rs"]
+mod tuning;
 
-use crate::minihasher::MinHasher;
-use crate::shingleset::ShingleSet;
+use self::lsh::{DEFAULT_FALSE_POSITIVE_WEIGHT, DEFAULT_MAX_PERM};
+use self::minihasher::MinHasher;
+use self::normalize::Normalization;
+use self::oph::OnePermutationHasher;
+use self::shingleset::{ShingleMode, ShingleSet};
 
-fn validate_constant_param<T: Copy + PartialEq>(
-    slice: &[T],
+fn parse_parameter<T: std::str::FromStr>(
+    bind: &BindInfo,
+    index: u64,
     param_name: &str,
 ) -> Result<T, Box<dyn Error>> {
-    let value = slice[0];
-    if !slice.iter().all(|&v| v == value) {
-        return Err(format!("{} must be a constant value, not vary per row", param_name).into());
+    let value = bind.get_parameter(index).to_string();
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", param_name, value).into())
+}
+
+fn build_hashers(band_count: usize, band_size: usize, seed: u64) -> Vec<MinHasher> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..band_count)
+        .map(|_| MinHasher::new(band_size, &mut rng))
+        .collect()
+}
+
+/// Upper bound on the number of cached families, so that per-row seeds cannot
+/// grow a [`HasherCache`] without limit.
+const MAX_CACHED_FAMILIES: usize = 1024;
+
+/// The `(band_count, band_size, seed)` parameters a hasher family is built from.
+type FamilyKey = (usize, usize, u64);
+
+/// MinHasher families keyed by `(band_count, band_size, seed)`, kept in the
+/// scalar function state so they are built once rather than for every row.
+#[derive(Default)]
+struct HasherCache {
+    families: RwLock<FxHashMap<FamilyKey, Arc<Vec<MinHasher>>>>,
+}
+
+impl HasherCache {
+    fn get(&self, band_count: usize, band_size: usize, seed: u64) -> Arc<Vec<MinHasher>> {
+        let key = (band_count, band_size, seed);
+        if let Some(family) = self
+            .families
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&key)
+        {
+            return family.clone();
+        }
+
+        let family = Arc::new(build_hashers(band_count, band_size, seed));
+        let mut families = self
+            .families
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if families.len() >= MAX_CACHED_FAMILIES {
+            families.clear();
+        }
+        families.entry(key).or_insert(family).clone()
+    }
+}
+
+fn validate_positive(value: usize, param_name: &str) -> Result<usize, Box<dyn Error>> {
+    if value == 0 {
+        return Err(format!("{} must be greater than 0", param_name).into());
+    }
+    Ok(value)
+}
+
+/// Seeds are `BIGINT`s throughout, so that integer literals bind everywhere.
+fn validate_seed(value: i64) -> Result<u64, Box<dyn Error>> {
+    if value < 0 {
+        return Err("seed must not be negative".into());
+    }
+    Ok(value as u64)
+}
+
+fn validate_unit_interval(value: f64, param_name: &str) -> Result<f64, Box<dyn Error>> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(format!("{} must be between 0 and 1", param_name).into());
     }
     Ok(value)
 }
 
+fn band_hashes(shingle_set: &ShingleSet, hashers: &[MinHasher]) -> Vec<u64> {
+    hashers
+        .iter()
+        .map(|hasher| hasher.hash(shingle_set))
+        .collect()
+}
+
+fn check_signature_lengths(a: &[u64], b: &[u64]) -> Result<(), Box<dyn Error>> {
+    if a.len() != b.len() {
+        return Err(format!(
+            "signatures must have the same length, got {} and {}",
+            a.len(),
+            b.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Lowers `target` to the elementwise minimum of both signatures, which is the
+/// signature of the union of their sets.
+fn merge_signatures(target: &mut [u64], source: &[u64]) -> Result<(), Box<dyn Error>> {
+    check_signature_lengths(target, source)?;
+    for (minimum, &other) in target.iter_mut().zip(source) {
+        *minimum = (*minimum).min(other);
+    }
+    Ok(())
+}
+
+/// Estimated Jaccard similarity of two non-empty signatures of the same length:
+/// the fraction of equal slots.
+fn signature_similarity(a: &[u64], b: &[u64]) -> f64 {
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / a.len() as f64
+}
+
+/// Estimated number of distinct shingles behind a non-empty signature.
+///
+/// Each slot is the minimum of `n` roughly uniform hashes, so `-ln(1 - min / 2^64)`
+/// is exponentially distributed with rate `n`, and `(k - 1) / sum` over the `k`
+/// slots is an unbiased estimate of `n`.
+fn estimate_cardinality(signature: &[u64]) -> f64 {
+    let sum: f64 = signature
+        .iter()
+        .map(|&minimum| {
+            let uniform = minimum as f64 / u64::MAX as f64;
+            -(-uniform).ln_1p()
+        })
+        .sum();
+    if sum == 0.0 {
+        return f64::INFINITY;
+    }
+    (signature.len() as f64 - 1.0).max(1.0) / sum
+}
+
+/// Reads the elements of every list in column `col_idx`; NULL lists are `None`.
+unsafe fn read_lists<'a, T: Copy>(
+    input: &'a DataChunkHandle,
+    col_idx: usize,
+    name: &str,
+) -> Result<Vec<Option<&'a [T]>>, Box<dyn Error>> {
+    read_list_vector(
+        ffi::duckdb_data_chunk_get_vector(input.get_ptr(), col_idx as u64),
+        input.len(),
+        name,
+    )
+}
+
+/// Reads the elements of the first `len` lists in `vector`; NULL lists are
+/// `None`, and lists with NULL elements are rejected.
+unsafe fn read_list_vector<'a, T: Copy>(
+    vector: ffi::duckdb_vector,
+    len: usize,
+    name: &str,
+) -> Result<Vec<Option<&'a [T]>>, Box<dyn Error>> {
+    let validity = FlatVector::from(vector);
+    let entries = std::slice::from_raw_parts(
+        ffi::duckdb_vector_get_data(vector) as *const ffi::duckdb_list_entry,
+        len,
+    );
+    let child = ffi::duckdb_list_vector_get_child(vector);
+    let child_validity = FlatVector::from(child);
+    let child_data = ffi::duckdb_vector_get_data(child) as *const T;
+    entries
+        .iter()
+        .enumerate()
+        .map(|(row_idx, entry)| {
+            if validity.row_is_null(row_idx as u64) {
+                return Ok(None);
+            }
+            if entry.length == 0 {
+                return Ok(Some(&[][..]));
+            }
+            let start = entry.offset as usize;
+            let end = start + entry.length as usize;
+            if (start..end).any(|idx| child_validity.row_is_null(idx as u64)) {
+                return Err(format!("{} must not contain NULL elements", name).into());
+            }
+            Ok(Some(std::slice::from_raw_parts(
+                child_data.add(start),
+                entry.length as usize,
+            )))
+        })
+        .collect()
+}
+
+/// The elements of a `LIST<VARCHAR>`; NULL elements are `None`.
+type StringList = Vec<Option<String>>;
+
+/// Reads every list of strings in column `col_idx`; NULL lists are `None`.
+unsafe fn read_string_lists(input: &DataChunkHandle, col_idx: usize) -> Vec<Option<StringList>> {
+    let vector = ffi::duckdb_data_chunk_get_vector(input.get_ptr(), col_idx as u64);
+    let validity = input.flat_vector(col_idx);
+    let entries = std::slice::from_raw_parts(
+        ffi::duckdb_vector_get_data(vector) as *const ffi::duckdb_list_entry,
+        input.len(),
+    );
+    let child = FlatVector::from(ffi::duckdb_list_vector_get_child(vector));
+    let strings = child
+        .as_slice_with_len::<duckdb_string_t>(ffi::duckdb_list_vector_get_size(vector) as usize);
+    entries
+        .iter()
+        .enumerate()
+        .map(|(row_idx, entry)| {
+            if validity.row_is_null(row_idx as u64) {
+                return None;
+            }
+            let start = entry.offset as usize;
+            let end = start + entry.length as usize;
+            Some(
+                (start..end)
+                    .map(|idx| {
+                        (!child.row_is_null(idx as u64))
+                            .then(|| DuckString::new(&mut { strings[idx] }).as_str().to_string())
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// The entries of a `MAP(VARCHAR, DOUBLE)`.
+type WeightMap = Vec<(String, f64)>;
+
+/// Reads every map in column `col_idx`; NULL maps are `None` and entries with
+/// a NULL weight are left out.
+unsafe fn read_weight_maps(input: &DataChunkHandle, col_idx: usize) -> Vec<Option<WeightMap>> {
+    let vector = ffi::duckdb_data_chunk_get_vector(input.get_ptr(), col_idx as u64);
+    let validity = input.flat_vector(col_idx);
+    let entries = std::slice::from_raw_parts(
+        ffi::duckdb_vector_get_data(vector) as *const ffi::duckdb_list_entry,
+        input.len(),
+    );
+    let child = ffi::duckdb_list_vector_get_child(vector);
+    let size = ffi::duckdb_list_vector_get_size(vector) as usize;
+    let keys = FlatVector::from(ffi::duckdb_struct_vector_get_child(child, 0));
+    let values = FlatVector::from(ffi::duckdb_struct_vector_get_child(child, 1));
+    let key_strings = keys.as_slice_with_len::<duckdb_string_t>(size);
+    let weights = values.as_slice_with_len::<f64>(size);
+    entries
+        .iter()
+        .enumerate()
+        .map(|(row_idx, entry)| {
+            if validity.row_is_null(row_idx as u64) {
+                return None;
+            }
+            let start = entry.offset as usize;
+            let end = start + entry.length as usize;
+            Some(
+                (start..end)
+                    .filter(|&idx| !values.row_is_null(idx as u64))
+                    .map(|idx| {
+                        let key = DuckString::new(&mut { key_strings[idx] })
+                            .as_str()
+                            .to_string();
+                        (key, weights[idx])
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Writes one list per row to `output`; `None` rows become NULL.
+fn write_lists<T: Copy>(output: &mut dyn WritableVector, lists: &[Option<Vec<T>>]) {
+    write_lists_at(output, lists, 0);
+}
+
+/// Like [`write_lists`], but starting at row `row_offset` and appending to the
+/// elements already in `output`.
+fn write_lists_at<T: Copy>(
+    output: &mut dyn WritableVector,
+    lists: &[Option<Vec<T>>],
+    row_offset: usize,
+) {
+    let mut output_lists = output.list_vector();
+    let mut offset = output_lists.len();
+    let total_len: usize = offset + lists.iter().flatten().map(Vec::len).sum::<usize>();
+    let mut child = output_lists.child(total_len);
+    let values: &mut [T] = child.as_mut_slice_with_len(total_len);
+    for (row_idx, list) in lists.iter().enumerate() {
+        match list {
+            Some(list) => {
+                values[offset..offset + list.len()].copy_from_slice(list);
+                output_lists.set_entry(row_offset + row_idx, offset, list.len());
+                offset += list.len();
+            }
+            None => {
+                output_lists.set_entry(row_offset + row_idx, offset, 0);
+                output_lists.set_null(row_offset + row_idx);
+            }
+        }
+    }
+    output_lists.set_len(total_len);
+}
+
+/// `(string, ngram_width, band_count, band_size, seed [, normalize [, salt]])`, returning
+/// a list of `hash_type` band hashes.
+fn minhash_signatures(hash_type: LogicalTypeId) -> Vec<ScalarFunctionSignature> {
+    let hash_type: LogicalTypeHandle = hash_type.into();
+    let parameters = || -> Vec<LogicalTypeHandle> {
+        vec![
+            LogicalTypeId::Varchar.into(),
+            LogicalTypeId::UBigint.into(),
+            LogicalTypeId::UBigint.into(),
+            LogicalTypeId::UBigint.into(),
+            LogicalTypeId::Bigint.into(),
+        ]
+    };
+    let mut with_normalize = parameters();
+    with_normalize.push(LogicalTypeId::Varchar.into());
+    let mut with_salt = parameters();
+    with_salt.push(LogicalTypeId::Varchar.into());
+    with_salt.push(LogicalTypeId::Varchar.into());
+    vec![
+        ScalarFunctionSignature::exact(parameters(), LogicalTypeHandle::list(&hash_type)),
+        ScalarFunctionSignature::exact(with_normalize, LogicalTypeHandle::list(&hash_type)),
+        ScalarFunctionSignature::exact(with_salt, LogicalTypeHandle::list(&hash_type)),
+    ]
+}
+
 unsafe fn minhash_invoke(
+    cache: &HasherCache,
     input: &mut DataChunkHandle,
     output: &mut dyn WritableVector,
     truncate: bool,
+    mode: ShingleMode,
+    one_permutation: bool,
 ) -> Result<(), Box<dyn Error>> {
     let input_strings = input.flat_vector(0);
     let input_ngram_width = input.flat_vector(1);
@@ -42,68 +388,379 @@ unsafe fn minhash_invoke(
     let input_band_size = input.flat_vector(3);
     let input_seed = input.flat_vector(4);
 
-    let strings = input_strings
-        .as_slice_with_len::<duckdb_string_t>(input.len())
-        .iter()
-        .map(|ptr| DuckString::new(&mut { *ptr }).as_str().to_string());
+    let strings = input_strings.as_slice_with_len::<duckdb_string_t>(input.len());
+    let ngram_widths = input_ngram_width.as_slice_with_len::<usize>(input.len());
+    let band_counts = input_band_count.as_slice_with_len::<usize>(input.len());
+    let band_sizes = input_band_size.as_slice_with_len::<usize>(input.len());
+    let seeds = input_seed.as_slice_with_len::<i64>(input.len());
+    let input_normalize = (input.num_columns() > 5).then(|| input.flat_vector(5));
+    let input_salt = (input.num_columns() > 6).then(|| input.flat_vector(6));
 
-    let ngram_width = validate_constant_param(
-        input_ngram_width.as_slice_with_len::<usize>(input.len()),
-        "ngram_width",
-    )?;
+    let columns: Vec<_> = (0..input.num_columns())
+        .map(|col_idx| input.flat_vector(col_idx))
+        .collect();
+    let mut family: Option<(FamilyKey, Arc<Vec<MinHasher>>)> = None;
+    let mut normalization: Option<(String, Normalization)> = None;
 
-    let band_count = validate_constant_param(
-        input_band_count.as_slice_with_len::<usize>(input.len()),
-        "band_count",
-    )?;
+    let mut row_hashes = Vec::with_capacity(input.len());
+    for row_idx in 0..input.len() {
+        // A NULL normalization means none, so that a salt can be passed alone.
+        if columns
+            .iter()
+            .enumerate()
+            .any(|(col_idx, vector)| col_idx != 5 && vector.row_is_null(row_idx as u64))
+        {
+            row_hashes.push(None);
+            continue;
+        }
+        let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+        let key = (
+            validate_positive(band_counts[row_idx], "band_count")?,
+            validate_positive(band_sizes[row_idx], "band_size")?,
+            validate_seed(seeds[row_idx])?,
+        );
+        let mut string = DuckString::new(&mut { strings[row_idx] })
+            .as_str()
+            .to_string();
+        if let Some(input_normalize) = input_normalize
+            .as_ref()
+            .filter(|vector| !vector.row_is_null(row_idx as u64))
+        {
+            let options = DuckString::new(&mut {
+                input_normalize.as_slice_with_len::<duckdb_string_t>(input.len())[row_idx]
+            })
+            .as_str()
+            .to_string();
+            let normalization = match &normalization {
+                Some((cached_options, normalization)) if *cached_options == options => {
+                    normalization
+                }
+                _ => {
+                    let parsed = Normalization::parse(&options)?;
+                    &normalization.insert((options, parsed)).1
+                }
+            };
+            string = normalization.apply(&string);
+        }
+        let salt = input_salt.as_ref().map(|input_salt| {
+            DuckString::new(&mut {
+                input_salt.as_slice_with_len::<duckdb_string_t>(input.len())[row_idx]
+            })
+            .as_str()
+            .to_string()
+        });
+        // An empty salt leaves the shingles unchanged.
+        let salt = salt.as_deref().filter(|salt| !salt.is_empty());
+        let shingle_set = ShingleSet::with_mode(&string, ngram_width, row_idx, salt, mode);
+        // Strings shorter than ngram_width have no shingles; hashing them would
+        // make all of them collide, so they yield NULL instead.
+        if shingle_set.shingles.is_empty() {
+            row_hashes.push(None);
+            continue;
+        }
+        if one_permutation {
+            let (band_count, band_size, seed) = key;
+            let num_bins = band_count.checked_mul(band_size).ok_or(format!(
+                "band_count * band_size is too large, got {} * {}",
+                band_count, band_size
+            ))?;
+            let hasher = OnePermutationHasher::new(num_bins, seed);
+            row_hashes.push(Some(hasher.band_hashes(&shingle_set, band_size)));
+            continue;
+        }
+        // Parameters are usually constant, so only consult the cache when they change.
+        let hashers = match &family {
+            Some((family_key, hashers)) if *family_key == key => hashers,
+            _ => &family.insert((key, cache.get(key.0, key.1, key.2))).1,
+        };
+        row_hashes.push(Some(band_hashes(&shingle_set, hashers)));
+    }
 
-    let band_size = validate_constant_param(
-        input_band_size.as_slice_with_len::<usize>(input.len()),
-        "band_size",
-    )?;
+    if truncate {
+        let truncated: Vec<Option<Vec<u32>>> = row_hashes
+            .into_iter()
+            .map(|hashes| Some(hashes?.into_iter().map(|hash| hash as u32).collect()))
+            .collect();
+        write_lists(output, &truncated);
+    } else {
+        write_lists(output, &row_hashes);
+    }
 
-    let seed = validate_constant_param(input_seed.as_slice_with_len::<u64>(input.len()), "seed")?;
+    Ok(())
+}
 
-    let mut output_hashes = output.list_vector();
-    let total_len: usize = band_count * input.len();
-    let mut hashes_vec = output_hashes.child(total_len);
+struct MinHash {}
 
-    if truncate {
-        let hashes: &mut [u32] = hashes_vec.as_mut_slice_with_len(total_len);
-        let mut offset = 0;
-        for (row_idx, string) in strings.enumerate().take(input.len()) {
-            let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
-            let mut rng = StdRng::seed_from_u64(seed);
-            for band_idx in 0..band_count {
-                let hasher = MinHasher::new(band_size, &mut rng);
-                hashes[offset + band_idx] = hasher.hash(&shingle_set) as u32;
+impl VScalar for MinHash {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        minhash_invoke(
+            state,
+            input,
+            output,
+            /*truncate=*/ false,
+            ShingleMode::Chars,
+            /*one_permutation=*/ false,
+        )
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        minhash_signatures(LogicalTypeId::UBigint)
+    }
+}
+
+struct MinHash32 {}
+
+impl VScalar for MinHash32 {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        minhash_invoke(
+            state,
+            input,
+            output,
+            /*truncate=*/ true,
+            ShingleMode::Chars,
+            /*one_permutation=*/ false,
+        )
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        minhash_signatures(LogicalTypeId::UInteger)
+    }
+}
+
+struct MinHashWords {}
+
+impl VScalar for MinHashWords {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        minhash_invoke(
+            state,
+            input,
+            output,
+            /*truncate=*/ false,
+            ShingleMode::Words,
+            /*one_permutation=*/ false,
+        )
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        minhash_signatures(LogicalTypeId::UBigint)
+    }
+}
+
+struct MinHashOph {}
+
+impl VScalar for MinHashOph {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        minhash_invoke(
+            state,
+            input,
+            output,
+            /*truncate=*/ false,
+            ShingleMode::Chars,
+            /*one_permutation=*/ true,
+        )
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        minhash_signatures(LogicalTypeId::UBigint)
+    }
+}
+
+/// Largest field weight accepted by `minhash_record`. A field of weight `k` is
+/// shingled `k` times, so unbounded weights would make a single row arbitrarily
+/// expensive.
+const MAX_FIELD_WEIGHT: i64 = 1024;
+
+struct MinHashRecord {}
+
+impl VScalar for MinHashRecord {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let records = read_string_lists(input, 0);
+        let input_ngram_width = input.flat_vector(1);
+        let input_band_count = input.flat_vector(2);
+        let input_band_size = input.flat_vector(3);
+        let input_seed = input.flat_vector(4);
+        let weights = (input.num_columns() > 5)
+            .then(|| read_lists::<i64>(input, 5, "weights"))
+            .transpose()?;
+
+        let ngram_widths = input_ngram_width.as_slice_with_len::<usize>(input.len());
+        let band_counts = input_band_count.as_slice_with_len::<usize>(input.len());
+        let band_sizes = input_band_size.as_slice_with_len::<usize>(input.len());
+        let seeds = input_seed.as_slice_with_len::<i64>(input.len());
+
+        let columns: Vec<_> = (1..input.num_columns())
+            .map(|col_idx| input.flat_vector(col_idx))
+            .collect();
+
+        let mut row_hashes = Vec::with_capacity(input.len());
+        for (row_idx, record) in records.iter().enumerate() {
+            let Some(record) = record else {
+                row_hashes.push(None);
+                continue;
+            };
+            if columns
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                row_hashes.push(None);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let band_count = validate_positive(band_counts[row_idx], "band_count")?;
+            let band_size = validate_positive(band_sizes[row_idx], "band_size")?;
+            let field_weights = match &weights {
+                Some(weights) => {
+                    let field_weights = weights[row_idx].unwrap_or_default();
+                    if field_weights.len() != record.len() {
+                        return Err(format!(
+                            "expected {} weights, one per field, got {}",
+                            record.len(),
+                            field_weights.len()
+                        )
+                        .into());
+                    }
+                    field_weights
+                }
+                None => &[],
+            };
+
+            // Each field is salted with its position so that equal text in
+            // different fields does not collide. Integer weights repeat the
+            // field's shingles under distinct salts, increasing its share of
+            // the combined set.
+            let mut shingle_set = ShingleSet::new("", ngram_width, row_idx, None);
+            for (field_idx, field) in record.iter().enumerate() {
+                let Some(field) = field else {
+                    continue;
+                };
+                let weight = field_weights.get(field_idx).copied().unwrap_or(1);
+                if !(0..=MAX_FIELD_WEIGHT).contains(&weight) {
+                    return Err(format!(
+                        "weights must be between 0 and {}, got {}",
+                        MAX_FIELD_WEIGHT, weight
+                    )
+                    .into());
+                }
+                for replica in 0..weight {
+                    let salt = format!("{}:{}", field_idx, replica);
+                    shingle_set.extend(&ShingleSet::new(field, ngram_width, row_idx, Some(&salt)));
+                }
             }
-            output_hashes.set_entry(row_idx, offset, band_count);
-            offset += band_count;
+            if shingle_set.shingles.is_empty() {
+                row_hashes.push(None);
+                continue;
+            }
+            let hashers = state.get(band_count, band_size, validate_seed(seeds[row_idx])?);
+            row_hashes.push(Some(band_hashes(&shingle_set, &hashers)));
         }
-        output_hashes.set_len(input.len());
-    } else {
-        let hashes: &mut [u64] = hashes_vec.as_mut_slice_with_len(total_len);
-        let mut offset = 0;
-        for (row_idx, string) in strings.enumerate().take(input.len()) {
-            let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
-            let mut rng = StdRng::seed_from_u64(seed);
-            for band_idx in 0..band_count {
-                let hasher = MinHasher::new(band_size, &mut rng);
-                hashes[offset + band_idx] = hasher.hash(&shingle_set);
+        write_lists(output, &row_hashes);
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        let parameters = || -> Vec<LogicalTypeHandle> {
+            vec![
+                LogicalTypeHandle::list(&LogicalTypeId::Varchar.into()),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::Bigint.into(),
+            ]
+        };
+        let mut with_weights = parameters();
+        with_weights.push(LogicalTypeHandle::list(&LogicalTypeId::Bigint.into()));
+        vec![
+            ScalarFunctionSignature::exact(
+                parameters(),
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+            ),
+            ScalarFunctionSignature::exact(
+                with_weights,
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+            ),
+        ]
+    }
+}
+
+struct Normalize {}
+
+impl VScalar for Normalize {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_strings = input.flat_vector(0);
+        let input_options = input.flat_vector(1);
+
+        let strings = input_strings.as_slice_with_len::<duckdb_string_t>(input.len());
+        let options = input_options.as_slice_with_len::<duckdb_string_t>(input.len());
+
+        let mut output_strings = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if input_strings.row_is_null(row_idx as u64)
+                || input_options.row_is_null(row_idx as u64)
+            {
+                output_strings.set_null(row_idx);
+                continue;
             }
-            output_hashes.set_entry(row_idx, offset, band_count);
-            offset += band_count;
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            let options = DuckString::new(&mut { options[row_idx] })
+                .as_str()
+                .to_string();
+            let normalization = Normalization::parse(&options)?;
+            output_strings.insert(row_idx, normalization.apply(&string).as_str());
         }
-        output_hashes.set_len(input.len());
+
+        Ok(())
     }
 
-    Ok(())
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![LogicalTypeId::Varchar.into(), LogicalTypeId::Varchar.into()],
+            LogicalTypeId::Varchar.into(),
+        )]
+    }
 }
 
-struct MinHash {}
+struct Jaccard {}
 
-impl VScalar for MinHash {
+impl VScalar for Jaccard {
     type State = ();
 
     unsafe fn invoke(
@@ -111,26 +768,157 @@ impl VScalar for MinHash {
         input: &mut DataChunkHandle,
         output: &mut dyn WritableVector,
     ) -> Result<(), Box<dyn Error>> {
-        minhash_invoke(input, output, /*truncate=*/ false)
+        let input_a = input.flat_vector(0);
+        let input_b = input.flat_vector(1);
+        let input_ngram_width = input.flat_vector(2);
+
+        let strings_a = input_a.as_slice_with_len::<duckdb_string_t>(input.len());
+        let strings_b = input_b.as_slice_with_len::<duckdb_string_t>(input.len());
+        let ngram_widths = input_ngram_width.as_slice_with_len::<usize>(input.len());
+
+        let mut output_similarity = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if [&input_a, &input_b, &input_ngram_width]
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                output_similarity.set_null(row_idx);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let a = DuckString::new(&mut { strings_a[row_idx] })
+                .as_str()
+                .to_string();
+            let b = DuckString::new(&mut { strings_b[row_idx] })
+                .as_str()
+                .to_string();
+            let shingles_a = ShingleSet::new(&a, ngram_width, row_idx, None);
+            let shingles_b = ShingleSet::new(&b, ngram_width, row_idx, None);
+            // Like minhash, strings without shingles have no similarity to report.
+            if shingles_a.shingles.is_empty() || shingles_b.shingles.is_empty() {
+                output_similarity.set_null(row_idx);
+                continue;
+            }
+            output_similarity.as_mut_slice::<f64>()[row_idx] =
+                shingles_a.jaccard_similarity(&shingles_b);
+        }
+
+        Ok(())
     }
 
     fn signatures() -> Vec<ScalarFunctionSignature> {
         vec![ScalarFunctionSignature::exact(
             vec![
+                LogicalTypeId::Varchar.into(),
                 LogicalTypeId::Varchar.into(),
                 LogicalTypeId::UBigint.into(),
+            ],
+            LogicalTypeId::Double.into(),
+        )]
+    }
+}
+
+/// State of `minhash_auto`: the hasher families plus the banding tuned for
+/// each threshold, since tuning integrates the S-curve for every candidate.
+#[derive(Default)]
+struct AutoState {
+    hashers: HasherCache,
+    params: RwLock<FxHashMap<u64, (usize, usize)>>,
+}
+
+impl AutoState {
+    fn params(&self, threshold: f64) -> (usize, usize) {
+        let key = threshold.to_bits();
+        if let Some(&params) = self
+            .params
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&key)
+        {
+            return params;
+        }
+
+        let params =
+            lsh::optimal_params(threshold, DEFAULT_FALSE_POSITIVE_WEIGHT, DEFAULT_MAX_PERM);
+        let mut cached = self.params.write().unwrap_or_else(PoisonError::into_inner);
+        if cached.len() >= MAX_CACHED_FAMILIES {
+            cached.clear();
+        }
+        *cached.entry(key).or_insert(params)
+    }
+}
+
+struct MinHashAuto {}
+
+impl VScalar for MinHashAuto {
+    type State = AutoState;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_strings = input.flat_vector(0);
+        let input_ngram_width = input.flat_vector(1);
+        let input_threshold = input.flat_vector(2);
+        let input_seed = input.flat_vector(3);
+
+        let strings = input_strings.as_slice_with_len::<duckdb_string_t>(input.len());
+        let ngram_widths = input_ngram_width.as_slice_with_len::<usize>(input.len());
+        let thresholds = input_threshold.as_slice_with_len::<f64>(input.len());
+        let seeds = input_seed.as_slice_with_len::<i64>(input.len());
+
+        let mut row_hashes = Vec::with_capacity(input.len());
+        for row_idx in 0..input.len() {
+            if [
+                &input_strings,
+                &input_ngram_width,
+                &input_threshold,
+                &input_seed,
+            ]
+            .iter()
+            .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                row_hashes.push(None);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let threshold = validate_unit_interval(thresholds[row_idx], "threshold")?;
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
+            if shingle_set.shingles.is_empty() {
+                row_hashes.push(None);
+                continue;
+            }
+            let (band_count, band_size) = state.params(threshold);
+            let hashers = state
+                .hashers
+                .get(band_count, band_size, validate_seed(seeds[row_idx])?);
+            row_hashes.push(Some(band_hashes(&shingle_set, &hashers)));
+        }
+        write_lists(output, &row_hashes);
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeId::Varchar.into(),
                 LogicalTypeId::UBigint.into(),
-                LogicalTypeId::UBigint.into(),
-                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::Double.into(),
+                LogicalTypeId::Bigint.into(),
             ],
             LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
         )]
     }
 }
 
-struct MinHash32 {}
+struct CollisionProbability {}
 
-impl VScalar for MinHash32 {
+impl VScalar for CollisionProbability {
     type State = ();
 
     unsafe fn invoke(
@@ -138,28 +926,946 @@ impl VScalar for MinHash32 {
         input: &mut DataChunkHandle,
         output: &mut dyn WritableVector,
     ) -> Result<(), Box<dyn Error>> {
-        minhash_invoke(input, output, /*truncate=*/ true)
+        let input_similarity = input.flat_vector(0);
+        let input_band_count = input.flat_vector(1);
+        let input_band_size = input.flat_vector(2);
+
+        let similarities = input_similarity.as_slice_with_len::<f64>(input.len());
+        let band_counts = input_band_count.as_slice_with_len::<usize>(input.len());
+        let band_sizes = input_band_size.as_slice_with_len::<usize>(input.len());
+
+        let mut output_probability = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if [&input_similarity, &input_band_count, &input_band_size]
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                output_probability.set_null(row_idx);
+                continue;
+            }
+            let similarity = validate_unit_interval(similarities[row_idx], "similarity")?;
+            let band_count = validate_positive(band_counts[row_idx], "band_count")?;
+            let band_size = validate_positive(band_sizes[row_idx], "band_size")?;
+            output_probability.as_mut_slice::<f64>()[row_idx] =
+                lsh::collision_probability(similarity, band_count, band_size);
+        }
+
+        Ok(())
     }
 
     fn signatures() -> Vec<ScalarFunctionSignature> {
         vec![ScalarFunctionSignature::exact(
             vec![
-                LogicalTypeId::Varchar.into(),
+                LogicalTypeId::Double.into(),
                 LogicalTypeId::UBigint.into(),
                 LogicalTypeId::UBigint.into(),
+            ],
+            LogicalTypeId::Double.into(),
+        )]
+    }
+}
+
+struct MinHashSignature {}
+
+impl VScalar for MinHashSignature {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_strings = input.flat_vector(0);
+        let input_ngram_width = input.flat_vector(1);
+        let input_num_perm = input.flat_vector(2);
+        let input_seed = input.flat_vector(3);
+
+        let strings = input_strings.as_slice_with_len::<duckdb_string_t>(input.len());
+        let ngram_widths = input_ngram_width.as_slice_with_len::<usize>(input.len());
+
+        let num_perms = input_num_perm.as_slice_with_len::<usize>(input.len());
+        let seeds = input_seed.as_slice_with_len::<i64>(input.len());
+
+        let mut signatures = Vec::with_capacity(input.len());
+        for row_idx in 0..input.len() {
+            if [
+                &input_strings,
+                &input_ngram_width,
+                &input_num_perm,
+                &input_seed,
+            ]
+            .iter()
+            .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                signatures.push(None);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let num_perm = validate_positive(num_perms[row_idx], "num_perm")?;
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
+            if shingle_set.shingles.is_empty() {
+                signatures.push(None);
+                continue;
+            }
+            // A signature is a single hasher with `num_perm` seeds, i.e. a one-band family.
+            let hasher = &state.get(1, num_perm, validate_seed(seeds[row_idx])?)[0];
+            signatures.push(Some(hasher.signature(&shingle_set)));
+        }
+        write_lists(output, &signatures);
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeId::Varchar.into(),
                 LogicalTypeId::UBigint.into(),
                 LogicalTypeId::UBigint.into(),
+                LogicalTypeId::Bigint.into(),
             ],
-            LogicalTypeHandle::list(&LogicalTypeId::UInteger.into()),
+            LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
         )]
     }
 }
 
-#[duckdb_entrypoint_c_api()]
-pub unsafe fn extension_entrypoint(con: Connection) -> Result<(), Box<dyn Error>> {
-    con.register_scalar_function::<MinHash>("minhash")
-        .expect("Failed to register minhash function");
-    con.register_scalar_function::<MinHash32>("minhash32")
-        .expect("Failed to register minhash32 function");
+struct BbitMinHash {}
+
+impl VScalar for BbitMinHash {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_strings = input.flat_vector(0);
+        let input_ngram_width = input.flat_vector(1);
+        let input_num_perm = input.flat_vector(2);
+        let input_seed = input.flat_vector(3);
+        let input_bits = input.flat_vector(4);
+
+        let strings = input_strings.as_slice_with_len::<duckdb_string_t>(input.len());
+        let ngram_widths = input_ngram_width.as_slice_with_len::<usize>(input.len());
+        let num_perms = input_num_perm.as_slice_with_len::<usize>(input.len());
+        let seeds = input_seed.as_slice_with_len::<i64>(input.len());
+        let bits = input_bits.as_slice_with_len::<usize>(input.len());
+
+        let mut output_blobs = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if [
+                &input_strings,
+                &input_ngram_width,
+                &input_num_perm,
+                &input_seed,
+                &input_bits,
+            ]
+            .iter()
+            .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                output_blobs.set_null(row_idx);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let num_perm = validate_positive(num_perms[row_idx], "num_perm")?;
+            let bits = bbit::validate_bits(bits[row_idx])?;
+            if !(num_perm * bits as usize).is_multiple_of(8) {
+                return Err(format!(
+                    "num_perm * b must be a multiple of 8, got {} * {}",
+                    num_perm, bits
+                )
+                .into());
+            }
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
+            if shingle_set.shingles.is_empty() {
+                output_blobs.set_null(row_idx);
+                continue;
+            }
+            let hasher = &state.get(1, num_perm, validate_seed(seeds[row_idx])?)[0];
+            let packed = bbit::pack(&hasher.signature(&shingle_set), bits);
+            output_blobs.insert(row_idx, packed.as_slice());
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeId::Varchar.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::Bigint.into(),
+                LogicalTypeId::UBigint.into(),
+            ],
+            LogicalTypeId::Blob.into(),
+        )]
+    }
+}
+
+struct BbitMinHashSimilarity {}
+
+impl VScalar for BbitMinHashSimilarity {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_a = input.flat_vector(0);
+        let input_b = input.flat_vector(1);
+        let input_bits = input.flat_vector(2);
+
+        let blobs_a = input_a.as_slice_with_len::<duckdb_string_t>(input.len());
+        let blobs_b = input_b.as_slice_with_len::<duckdb_string_t>(input.len());
+        let bits = input_bits.as_slice_with_len::<usize>(input.len());
+
+        let mut output_similarity = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if [&input_a, &input_b, &input_bits]
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                output_similarity.set_null(row_idx);
+                continue;
+            }
+            let bits = bbit::validate_bits(bits[row_idx])?;
+            let (mut blob_a, mut blob_b) = (blobs_a[row_idx], blobs_b[row_idx]);
+            let a = DuckString::new(&mut blob_a).as_bytes();
+            let b = DuckString::new(&mut blob_b).as_bytes();
+            if a.len() != b.len() {
+                return Err(format!(
+                    "signatures must have the same length, got {} and {} bytes",
+                    a.len(),
+                    b.len()
+                )
+                .into());
+            }
+            if a.is_empty() {
+                output_similarity.set_null(row_idx);
+                continue;
+            }
+            output_similarity.as_mut_slice::<f64>()[row_idx] = bbit::similarity(a, b, bits);
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeId::Blob.into(),
+                LogicalTypeId::Blob.into(),
+                LogicalTypeId::UBigint.into(),
+            ],
+            LogicalTypeId::Double.into(),
+        )]
+    }
+}
+
+struct MinHashSimilarity {}
+
+impl VScalar for MinHashSimilarity {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let signatures_a = read_lists::<u64>(input, 0, "signature")?;
+        let signatures_b = read_lists::<u64>(input, 1, "signature")?;
+
+        let mut output_similarity = output.flat_vector();
+        for (row_idx, (a, b)) in signatures_a.iter().zip(&signatures_b).enumerate() {
+            let (Some(a), Some(b)) = (a, b) else {
+                output_similarity.set_null(row_idx);
+                continue;
+            };
+            check_signature_lengths(a, b)?;
+            if a.is_empty() {
+                output_similarity.set_null(row_idx);
+                continue;
+            }
+            output_similarity.as_mut_slice::<f64>()[row_idx] = signature_similarity(a, b);
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+            ],
+            LogicalTypeId::Double.into(),
+        )]
+    }
+}
+
+struct MinHashMerge {}
+
+impl VScalar for MinHashMerge {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let signatures_a = read_lists::<u64>(input, 0, "signature")?;
+        let signatures_b = read_lists::<u64>(input, 1, "signature")?;
+
+        let mut merged = Vec::with_capacity(input.len());
+        for (a, b) in signatures_a.iter().zip(&signatures_b) {
+            let (Some(a), Some(b)) = (a, b) else {
+                merged.push(None);
+                continue;
+            };
+            let mut signature = a.to_vec();
+            merge_signatures(&mut signature, b)?;
+            merged.push(Some(signature));
+        }
+        write_lists(output, &merged);
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+            ],
+            LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+        )]
+    }
+}
+
+struct MinHashCardinality {}
+
+impl VScalar for MinHashCardinality {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let signatures = read_lists::<u64>(input, 0, "signature")?;
+
+        let mut output_cardinality = output.flat_vector();
+        for (row_idx, signature) in signatures.iter().enumerate() {
+            match signature {
+                Some(signature) if !signature.is_empty() => {
+                    output_cardinality.as_mut_slice::<f64>()[row_idx] =
+                        estimate_cardinality(signature);
+                }
+                _ => output_cardinality.set_null(row_idx),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![LogicalTypeHandle::list(&LogicalTypeId::UBigint.into())],
+            LogicalTypeId::Double.into(),
+        )]
+    }
+}
+
+struct MinHashContainment {}
+
+impl VScalar for MinHashContainment {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let signatures_a = read_lists::<u64>(input, 0, "signature")?;
+        let input_cardinality_a = input.flat_vector(1);
+        let signatures_b = read_lists::<u64>(input, 2, "signature")?;
+        let input_cardinality_b = input.flat_vector(3);
+
+        let cardinalities_a = input_cardinality_a.as_slice_with_len::<f64>(input.len());
+        let cardinalities_b = input_cardinality_b.as_slice_with_len::<f64>(input.len());
+
+        let mut output_containment = output.flat_vector();
+        for (row_idx, (a, b)) in signatures_a.iter().zip(&signatures_b).enumerate() {
+            let (Some(a), Some(b)) = (a, b) else {
+                output_containment.set_null(row_idx);
+                continue;
+            };
+            if input_cardinality_a.row_is_null(row_idx as u64)
+                || input_cardinality_b.row_is_null(row_idx as u64)
+            {
+                output_containment.set_null(row_idx);
+                continue;
+            }
+            check_signature_lengths(a, b)?;
+            let (cardinality_a, cardinality_b) =
+                (cardinalities_a[row_idx], cardinalities_b[row_idx]);
+            if cardinality_a < 0.0 || cardinality_b < 0.0 {
+                return Err("cardinalities must not be negative".into());
+            }
+            if a.is_empty() || cardinality_a == 0.0 {
+                output_containment.set_null(row_idx);
+                continue;
+            }
+            // |A ∩ B| = J (|A| + |B|) / (1 + J), from J = |A ∩ B| / |A ∪ B|.
+            let jaccard = signature_similarity(a, b);
+            let intersection = jaccard * (cardinality_a + cardinality_b) / (1.0 + jaccard);
+            output_containment.as_mut_slice::<f64>()[row_idx] =
+                (intersection / cardinality_a).min(1.0);
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+                LogicalTypeId::Double.into(),
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+                LogicalTypeId::Double.into(),
+            ],
+            LogicalTypeId::Double.into(),
+        )]
+    }
+}
+
+/// Token weights as `(shingle, weight)` pairs with positive weights.
+fn token_weights(map: &[(String, f64)]) -> Result<Vec<(u32, f64)>, Box<dyn Error>> {
+    let mut weights: FxHashMap<u32, f64> = FxHashMap::default();
+    for (token, weight) in map {
+        if weight.is_nan() || *weight < 0.0 {
+            return Err(format!(
+                "weights must be non-negative, got {} for '{}'",
+                weight, token
+            )
+            .into());
+        }
+        *weights.entry(ShingleSet::token_shingle(token)).or_default() += weight;
+    }
+    Ok(weights
+        .into_iter()
+        .filter(|&(_, weight)| weight > 0.0)
+        .collect())
+}
+
+struct WeightedMinHash {}
+
+impl VScalar for WeightedMinHash {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        // `(text, ngram_width, ...)` weighs shingles by their frequency in the
+        // text, `(weights, ...)` takes explicit token weights.
+        let from_text = input.num_columns() == 5;
+        let param_offset = if from_text { 2 } else { 1 };
+        let input_band_count = input.flat_vector(param_offset);
+        let input_band_size = input.flat_vector(param_offset + 1);
+        let input_seed = input.flat_vector(param_offset + 2);
+
+        let band_counts = input_band_count.as_slice_with_len::<usize>(input.len());
+        let band_sizes = input_band_size.as_slice_with_len::<usize>(input.len());
+        let seeds = input_seed.as_slice_with_len::<i64>(input.len());
+
+        let columns: Vec<_> = (0..input.num_columns())
+            .map(|col_idx| input.flat_vector(col_idx))
+            .collect();
+        let (strings, ngram_widths, maps) = if from_text {
+            (
+                columns[0].as_slice_with_len::<duckdb_string_t>(input.len()),
+                columns[1].as_slice_with_len::<usize>(input.len()),
+                Vec::new(),
+            )
+        } else {
+            (&[][..], &[][..], read_weight_maps(input, 0))
+        };
+
+        let mut row_hashes = Vec::with_capacity(input.len());
+        for row_idx in 0..input.len() {
+            if columns
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                row_hashes.push(None);
+                continue;
+            }
+            let band_count = validate_positive(band_counts[row_idx], "band_count")?;
+            let band_size = validate_positive(band_sizes[row_idx], "band_size")?;
+            let weights = if from_text {
+                let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+                let string = DuckString::new(&mut { strings[row_idx] })
+                    .as_str()
+                    .to_string();
+                ShingleSet::weighted(&string, ngram_width)
+            } else {
+                token_weights(maps[row_idx].as_deref().unwrap_or_default())?
+            };
+            if weights.is_empty() {
+                row_hashes.push(None);
+                continue;
+            }
+            let hashers = state.get(band_count, band_size, validate_seed(seeds[row_idx])?);
+            row_hashes.push(Some(
+                hashers
+                    .iter()
+                    .map(|hasher| hasher.weighted_hash(&weights))
+                    .collect::<Vec<u64>>(),
+            ));
+        }
+        write_lists(output, &row_hashes);
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![
+            ScalarFunctionSignature::exact(
+                vec![
+                    LogicalTypeId::Varchar.into(),
+                    LogicalTypeId::UBigint.into(),
+                    LogicalTypeId::UBigint.into(),
+                    LogicalTypeId::UBigint.into(),
+                    LogicalTypeId::Bigint.into(),
+                ],
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+            ),
+            ScalarFunctionSignature::exact(
+                vec![
+                    LogicalTypeHandle::map(
+                        &LogicalTypeId::Varchar.into(),
+                        &LogicalTypeId::Double.into(),
+                    ),
+                    LogicalTypeId::UBigint.into(),
+                    LogicalTypeId::UBigint.into(),
+                    LogicalTypeId::Bigint.into(),
+                ],
+                LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+            ),
+        ]
+    }
+}
+
+/// `minhash_lsh_keys(string, ngram_width, band_count, band_size, seed)`: the
+/// band hashes of `minhash` as `(band, hash)` structs, where `band` is the
+/// 1-based position of the hash. Unnested, they are the rows of an LSH index
+/// table, and the keys of a probe to join with it.
+struct MinHashLshKeys {}
+
+impl VScalar for MinHashLshKeys {
+    type State = HasherCache;
+
+    unsafe fn invoke(
+        state: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let columns: Vec<_> = (0..input.num_columns())
+            .map(|col_idx| input.flat_vector(col_idx))
+            .collect();
+        let strings = columns[0].as_slice_with_len::<duckdb_string_t>(input.len());
+        let ngram_widths = columns[1].as_slice_with_len::<usize>(input.len());
+        let band_counts = columns[2].as_slice_with_len::<usize>(input.len());
+        let band_sizes = columns[3].as_slice_with_len::<usize>(input.len());
+        let seeds = columns[4].as_slice_with_len::<i64>(input.len());
+
+        let mut row_hashes = Vec::with_capacity(input.len());
+        for row_idx in 0..input.len() {
+            if columns
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                row_hashes.push(None);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let band_count = validate_positive(band_counts[row_idx], "band_count")?;
+            let band_size = validate_positive(band_sizes[row_idx], "band_size")?;
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
+            if shingle_set.shingles.is_empty() {
+                row_hashes.push(None);
+                continue;
+            }
+            let hashers = state.get(band_count, band_size, validate_seed(seeds[row_idx])?);
+            row_hashes.push(Some(band_hashes(&shingle_set, &hashers)));
+        }
+
+        let mut keys = output.list_vector();
+        let total_len: usize = row_hashes.iter().flatten().map(Vec::len).sum();
+        let entries = keys.struct_child(total_len);
+        let mut bands = entries.child(0, total_len);
+        let mut hashes = entries.child(1, total_len);
+        let bands = bands.as_mut_slice_with_len::<i32>(total_len);
+        let hashes = hashes.as_mut_slice_with_len::<u64>(total_len);
+        let mut offset = 0;
+        for (row_idx, row) in row_hashes.iter().enumerate() {
+            let Some(row) = row else {
+                keys.set_entry(row_idx, offset, 0);
+                keys.set_null(row_idx);
+                continue;
+            };
+            for (band_idx, &hash) in row.iter().enumerate() {
+                bands[offset + band_idx] = band_idx as i32 + 1;
+                hashes[offset + band_idx] = hash;
+            }
+            keys.set_entry(row_idx, offset, row.len());
+            offset += row.len();
+        }
+        keys.set_len(total_len);
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeId::Varchar.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::Bigint.into(),
+            ],
+            LogicalTypeHandle::list(&LogicalTypeHandle::struct_type(&[
+                ("band", LogicalTypeId::Integer.into()),
+                ("hash", LogicalTypeId::UBigint.into()),
+            ])),
+        )]
+    }
+}
+
+struct ShingleCount {}
+
+impl VScalar for ShingleCount {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_strings = input.flat_vector(0);
+        let input_ngram_width = input.flat_vector(1);
+
+        let strings = input_strings.as_slice_with_len::<duckdb_string_t>(input.len());
+        let ngram_widths = input_ngram_width.as_slice_with_len::<usize>(input.len());
+
+        let mut output_counts = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if input_strings.row_is_null(row_idx as u64)
+                || input_ngram_width.row_is_null(row_idx as u64)
+            {
+                output_counts.set_null(row_idx);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
+            output_counts.as_mut_slice::<i64>()[row_idx] = shingle_set.shingles.len() as i64;
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![LogicalTypeId::Varchar.into(), LogicalTypeId::UBigint.into()],
+            LogicalTypeId::Bigint.into(),
+        )]
+    }
+}
+
+struct SimHash {}
+
+impl VScalar for SimHash {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let columns: Vec<_> = (0..input.num_columns())
+            .map(|col_idx| input.flat_vector(col_idx))
+            .collect();
+        let strings = columns[0].as_slice_with_len::<duckdb_string_t>(input.len());
+        let ngram_widths = columns[1].as_slice_with_len::<usize>(input.len());
+        let bits = columns[2].as_slice_with_len::<usize>(input.len());
+        let seeds = columns[3].as_slice_with_len::<i64>(input.len());
+        let weighted = columns
+            .get(4)
+            .map(|vector| vector.as_slice_with_len::<bool>(input.len()));
+
+        let mut output_fingerprints = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if columns
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                output_fingerprints.set_null(row_idx);
+                continue;
+            }
+            let ngram_width = validate_positive(ngram_widths[row_idx], "ngram_width")?;
+            let bits = simhash::validate_bits(bits[row_idx])?;
+            let string = DuckString::new(&mut { strings[row_idx] })
+                .as_str()
+                .to_string();
+            let shingles = if weighted.is_some_and(|weighted| weighted[row_idx]) {
+                ShingleSet::weighted(&string, ngram_width)
+            } else {
+                let shingle_set = ShingleSet::new(&string, ngram_width, row_idx, None);
+                shingle_set
+                    .shingles
+                    .iter()
+                    .map(|&shingle| (shingle, 1.0))
+                    .collect()
+            };
+            if shingles.is_empty() {
+                output_fingerprints.set_null(row_idx);
+                continue;
+            }
+            output_fingerprints.as_mut_slice::<u64>()[row_idx] =
+                simhash::fingerprint(shingles, bits, validate_seed(seeds[row_idx])?);
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        let parameters = || -> Vec<LogicalTypeHandle> {
+            vec![
+                LogicalTypeId::Varchar.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::Bigint.into(),
+            ]
+        };
+        let mut with_weighted = parameters();
+        with_weighted.push(LogicalTypeId::Boolean.into());
+        vec![
+            ScalarFunctionSignature::exact(parameters(), LogicalTypeId::UBigint.into()),
+            ScalarFunctionSignature::exact(with_weighted, LogicalTypeId::UBigint.into()),
+        ]
+    }
+}
+
+struct SimHashBands {}
+
+impl VScalar for SimHashBands {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_fingerprint = input.flat_vector(0);
+        let input_bits = input.flat_vector(1);
+        let input_band_count = input.flat_vector(2);
+
+        let fingerprints = input_fingerprint.as_slice_with_len::<u64>(input.len());
+        let bits = input_bits.as_slice_with_len::<usize>(input.len());
+        let band_counts = input_band_count.as_slice_with_len::<usize>(input.len());
+
+        let mut row_bands = Vec::with_capacity(input.len());
+        for row_idx in 0..input.len() {
+            if [&input_fingerprint, &input_bits, &input_band_count]
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                row_bands.push(None);
+                continue;
+            }
+            let bits = simhash::validate_bits(bits[row_idx])?;
+            let band_count = validate_positive(band_counts[row_idx], "band_count")?;
+            if band_count > bits as usize {
+                return Err(format!(
+                    "band_count must be at most bits, got {} and {}",
+                    band_count, bits
+                )
+                .into());
+            }
+            row_bands.push(Some(simhash::bands(
+                fingerprints[row_idx],
+                bits,
+                band_count as u32,
+            )));
+        }
+        write_lists(output, &row_bands);
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+                LogicalTypeId::UBigint.into(),
+            ],
+            LogicalTypeHandle::list(&LogicalTypeId::UBigint.into()),
+        )]
+    }
+}
+
+struct HammingDistance {}
+
+impl VScalar for HammingDistance {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let input_a = input.flat_vector(0);
+        let input_b = input.flat_vector(1);
+
+        let fingerprints_a = input_a.as_slice_with_len::<u64>(input.len());
+        let fingerprints_b = input_b.as_slice_with_len::<u64>(input.len());
+
+        let mut output_distance = output.flat_vector();
+        for row_idx in 0..input.len() {
+            if input_a.row_is_null(row_idx as u64) || input_b.row_is_null(row_idx as u64) {
+                output_distance.set_null(row_idx);
+                continue;
+            }
+            output_distance.as_mut_slice::<i64>()[row_idx] =
+                (fingerprints_a[row_idx] ^ fingerprints_b[row_idx]).count_ones() as i64;
+        }
+
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![LogicalTypeId::UBigint.into(), LogicalTypeId::UBigint.into()],
+            LogicalTypeId::Bigint.into(),
+        )]
+    }
+}
+
+/// # Safety
+///
+/// Called by DuckDB when the extension is loaded.
+pub unsafe fn extension_entrypoint(db: ffi::duckdb_database) -> Result<(), Box<dyn Error>> {
+    let con = Connection::open_from_raw(db.cast())?;
+    con.register_scalar_function::<MinHash>("minhash")
+        .expect("Failed to register minhash function");
+    con.register_scalar_function::<MinHash32>("minhash32")
+        .expect("Failed to register minhash32 function");
+    con.register_scalar_function::<MinHashWords>("minhash_words")
+        .expect("Failed to register minhash_words function");
+    con.register_scalar_function::<MinHashOph>("minhash_oph")
+        .expect("Failed to register minhash_oph function");
+    con.register_scalar_function::<MinHashRecord>("minhash_record")
+        .expect("Failed to register minhash_record function");
+    con.register_scalar_function::<MinHashAuto>("minhash_auto")
+        .expect("Failed to register minhash_auto function");
+    con.register_scalar_function::<CollisionProbability>("minhash_collision_probability")
+        .expect("Failed to register minhash_collision_probability function");
+    con.register_scalar_function::<Normalize>("minhash_normalize")
+        .expect("Failed to register minhash_normalize function");
+    con.register_scalar_function::<Jaccard>("minhash_jaccard")
+        .expect("Failed to register minhash_jaccard function");
+    con.register_scalar_function::<MinHashSignature>("minhash_signature")
+        .expect("Failed to register minhash_signature function");
+    con.register_scalar_function::<MinHashSimilarity>("minhash_similarity")
+        .expect("Failed to register minhash_similarity function");
+    con.register_scalar_function::<MinHashMerge>("minhash_merge")
+        .expect("Failed to register minhash_merge function");
+    con.register_scalar_function::<MinHashCardinality>("minhash_cardinality")
+        .expect("Failed to register minhash_cardinality function");
+    con.register_scalar_function::<MinHashContainment>("minhash_containment")
+        .expect("Failed to register minhash_containment function");
+    con.register_scalar_function::<BbitMinHash>("bbit_minhash")
+        .expect("Failed to register bbit_minhash function");
+    con.register_scalar_function::<BbitMinHashSimilarity>("bbit_minhash_similarity")
+        .expect("Failed to register bbit_minhash_similarity function");
+    con.register_scalar_function::<WeightedMinHash>("weighted_minhash")
+        .expect("Failed to register weighted_minhash function");
+    con.register_scalar_function::<ShingleCount>("shingle_count")
+        .expect("Failed to register shingle_count function");
+    con.register_scalar_function::<SimHash>("simhash")
+        .expect("Failed to register simhash function");
+    con.register_scalar_function::<SimHashBands>("simhash_bands")
+        .expect("Failed to register simhash_bands function");
+    con.register_scalar_function::<HammingDistance>("hamming_distance")
+        .expect("Failed to register hamming_distance function");
+    con.register_scalar_function::<MinHashLshKeys>("minhash_lsh_keys")
+        .expect("Failed to register minhash_lsh_keys function");
+    con.register_table_function::<tuning::MinHashLshParams>("minhash_lsh_params")
+        .expect("Failed to register minhash_lsh_params function");
+    con.register_table_function::<tuning::MinHashCollisionCurve>("minhash_collision_curve")
+        .expect("Failed to register minhash_collision_curve function");
+    aggregate::register(db)?;
     Ok(())
 }
+
+/// Minimum DuckDB C API version the extension is built against.
+/// Set by the Makefile like for `duckdb_entrypoint_c_api`. Without it, the
+/// default is the DuckDB version the unstable C API is built against.
+const MINIMUM_DUCKDB_VERSION: &str = match option_env!("DUCKDB_EXTENSION_MIN_DUCKDB_VERSION") {
+    Some(version) => version,
+    None => "v1.4.1",
+};
+
+/// Entrypoint called by DuckDB, equivalent to the one generated by
+/// `duckdb_entrypoint_c_api` except that [`extension_entrypoint`] receives the
+/// raw database: duckdb-rs does not wrap aggregate functions, so they are
+/// registered through the C API on a connection of their own.
+///
+/// # Safety
+///
+/// Called by DuckDB when the extension is loaded. Named
+/// `<DUCKDB_EXTENSION_NAME>_init_c_api` by `build.rs`.
+#[export_name = env!("EXTENSION_ENTRYPOINT")]
+pub unsafe extern "C" fn extension_init_c_api(
+    info: ffi::duckdb_extension_info,
+    access: *const ffi::duckdb_extension_access,
+) -> bool {
+    let init_result = match ffi::duckdb_rs_extension_api_init(info, access, MINIMUM_DUCKDB_VERSION)
+    {
+        // Initialization fails without an error, e.g. on an API version mismatch.
+        Ok(false) => return false,
+        Ok(true) => {
+            let db = *(*access).get_database.unwrap()(info);
+            extension_entrypoint(db)
+        }
+        Err(err) => Err(err.into()),
+    };
+
+    if let Err(err) = init_result {
+        let message = CString::new(err.to_string()).unwrap_or_else(|_| {
+            c"An error occured but the extension failed to allocate memory for an error string"
+                .into()
+        });
+        (*access).set_error.unwrap()(info, message.as_ptr());
+        return false;
+    }
+    true
+}
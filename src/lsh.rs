@@ -0,0 +1,72 @@
+/// Weight of false positives used by `minhash_auto`.
+pub const DEFAULT_FALSE_POSITIVE_WEIGHT: f64 = 0.5;
+
+/// Number of permutations `minhash_auto` may spread over its bands.
+pub const DEFAULT_MAX_PERM: usize = 128;
+
+/// Number of intervals used to integrate the S-curve.
+const INTEGRATION_STEPS: usize = 1000;
+
+/// Probability that a pair with Jaccard similarity `similarity` shares at
+/// least one of `band_count` bands of `band_size` permutations.
+pub fn collision_probability(similarity: f64, band_count: usize, band_size: usize) -> f64 {
+    1.0 - (1.0 - similarity.powi(band_size as i32)).powi(band_count as i32)
+}
+
+/// Integrates `f` over `[start, end]` with Simpson's rule.
+fn integrate(f: impl Fn(f64) -> f64, start: f64, end: f64) -> f64 {
+    let step = (end - start) / INTEGRATION_STEPS as f64;
+    let interior: f64 = (1..INTEGRATION_STEPS)
+        .map(|i| {
+            let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+            weight * f(start + i as f64 * step)
+        })
+        .sum();
+    (f(start) + interior + f(end)) * step / 3.0
+}
+
+/// Area under the S-curve below `threshold`: pairs that become candidates
+/// although they are less similar than wanted.
+pub fn false_positive_probability(threshold: f64, band_count: usize, band_size: usize) -> f64 {
+    integrate(
+        |s| collision_probability(s, band_count, band_size),
+        0.0,
+        threshold,
+    )
+}
+
+/// Area above the S-curve from `threshold` on: pairs that are similar enough
+/// but never share a band.
+pub fn false_negative_probability(threshold: f64, band_count: usize, band_size: usize) -> f64 {
+    integrate(
+        |s| 1.0 - collision_probability(s, band_count, band_size),
+        threshold,
+        1.0,
+    )
+}
+
+/// Banding that minimizes the weighted sum of false positive and false
+/// negative probabilities for `threshold`, using at most `max_perm`
+/// permutations. Returns `(band_count, band_size)`.
+pub fn optimal_params(
+    threshold: f64,
+    false_positive_weight: f64,
+    max_perm: usize,
+) -> (usize, usize) {
+    let false_negative_weight = 1.0 - false_positive_weight;
+    let mut best = (1, 1);
+    let mut min_error = f64::INFINITY;
+    for band_count in 1..=max_perm {
+        for band_size in 1..=max_perm / band_count {
+            let error = false_positive_weight
+                * false_positive_probability(threshold, band_count, band_size)
+                + false_negative_weight
+                    * false_negative_probability(threshold, band_count, band_size);
+            if error < min_error {
+                min_error = error;
+                best = (band_count, band_size);
+            }
+        }
+    }
+    best
+}
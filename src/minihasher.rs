@@ -5,7 +5,7 @@ use ndarray_rand::rand_distr::Uniform;
 
 use rand::Rng;
 
-use crate::shingleset::ShingleSet;
+use super::shingleset::ShingleSet;
 
 #[derive(Debug)]
 pub struct MinHasher {
@@ -15,12 +15,13 @@ pub struct MinHasher {
 impl MinHasher {
     pub fn new<R: Rng>(band_width: usize, rand_state: &mut R) -> Self {
         let dist = Uniform::new(0, 20000000);
-        let seeds: Vec<u64> = (0..band_width).map(|_| rand_state.sample(&dist)).collect();
+        let seeds: Vec<u64> = (0..band_width).map(|_| rand_state.sample(dist)).collect();
         Self { seeds }
     }
 
-    pub fn hash(&self, shingle_set: &ShingleSet) -> u64 {
-        let mini_hashes = self.seeds.iter().map(|seed| {
+    /// The minimum hash of the shingle set under each seed.
+    fn mini_hashes<'a>(&'a self, shingle_set: &'a ShingleSet) -> impl Iterator<Item = u64> + 'a {
+        self.seeds.iter().map(|seed| {
             let mut min_hash_seen = u64::MAX;
             for item in &shingle_set.shingles {
                 let mut hasher = FxHasher::default();
@@ -35,12 +36,78 @@ impl MinHasher {
                 }
             }
             min_hash_seen
-        });
+        })
+    }
+
+    pub fn signature(&self, shingle_set: &ShingleSet) -> Vec<u64> {
+        self.mini_hashes(shingle_set).collect()
+    }
+
+    /// Lowers `minima` to the minimum hashes of `shingle_set` under each seed,
+    /// so that repeated calls yield the signature of the union of the sets.
+    pub fn update_minima(&self, minima: &mut [u64], shingle_set: &ShingleSet) {
+        for (minimum, mini_hash) in minima.iter_mut().zip(self.mini_hashes(shingle_set)) {
+            *minimum = (*minimum).min(mini_hash);
+        }
+    }
 
+    /// Collapses per-seed minima into a single band hash.
+    pub fn hash_minima(minima: impl IntoIterator<Item = u64>) -> u64 {
         let mut hasher = FxHasher::default();
-        for mini_hash in mini_hashes {
+        for mini_hash in minima {
             mini_hash.hash(&mut hasher);
         }
         hasher.finish()
     }
+
+    pub fn hash(&self, shingle_set: &ShingleSet) -> u64 {
+        Self::hash_minima(self.mini_hashes(shingle_set))
+    }
+
+    /// Weighted MinHash of `(shingle, weight)` pairs with positive weights, by
+    /// Improved Consistent Weighted Sampling (Ioffe, 2010). Each seed samples one
+    /// `(shingle, t)` pair, on which two weighted sets agree with probability
+    /// equal to their generalized Jaccard similarity `Σ min / Σ max`.
+    pub fn weighted_hash(&self, weights: &[(u32, f64)]) -> u64 {
+        let mut hasher = FxHasher::default();
+        for seed in &self.seeds {
+            let mut min_a = f64::INFINITY;
+            let mut sample = (0, 0);
+            for &(shingle, weight) in weights {
+                let mut state_hasher = FxHasher::default();
+                seed.hash(&mut state_hasher);
+                shingle.hash(&mut state_hasher);
+                let mut state = state_hasher.finish();
+
+                // r, c ~ Gamma(2, 1) and beta ~ Uniform(0, 1), fixed per (seed, shingle).
+                let r = -(unit_uniform(&mut state) * unit_uniform(&mut state)).ln();
+                let c = -(unit_uniform(&mut state) * unit_uniform(&mut state)).ln();
+                let beta = unit_uniform(&mut state);
+
+                let t = (weight.ln() / r + beta).floor();
+                let y = (r * (t - beta)).exp();
+                let a = c / (y * r.exp());
+                if a < min_a {
+                    min_a = a;
+                    sample = (shingle, t as i64);
+                }
+            }
+            sample.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// Next value of a SplitMix64 generator, mapped to the open interval (0, 1).
+fn unit_uniform(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    ((mix(*state) >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+}
+
+/// The SplitMix64 finalizer, which spreads every input bit over the whole
+/// output. The low bits of an `FxHasher` result are poorly mixed.
+pub fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
@@ -0,0 +1,72 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Preprocessing applied to a string before it is shingled.
+///
+/// Parsed from a comma-separated list of options, e.g. `'nfkc,lowercase,strip_accents'`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalization {
+    pub nfc: bool,
+    pub nfkc: bool,
+    pub lowercase: bool,
+    pub casefold: bool,
+    pub strip_accents: bool,
+    pub remove_punctuation: bool,
+    pub collapse_whitespace: bool,
+}
+
+impl Normalization {
+    pub fn parse(options: &str) -> Result<Self, String> {
+        let mut normalization = Self::default();
+        for option in options
+            .split(',')
+            .map(str::trim)
+            .filter(|option| !option.is_empty())
+        {
+            match option.to_ascii_lowercase().as_str() {
+                "nfc" => normalization.nfc = true,
+                "nfkc" => normalization.nfkc = true,
+                "lowercase" => normalization.lowercase = true,
+                "casefold" => normalization.casefold = true,
+                "strip_accents" => normalization.strip_accents = true,
+                "remove_punctuation" => normalization.remove_punctuation = true,
+                "collapse_whitespace" => normalization.collapse_whitespace = true,
+                _ => return Err(format!("unknown normalization option: {}", option)),
+            }
+        }
+        Ok(normalization)
+    }
+
+    pub fn apply(&self, string: &str) -> String {
+        let mut out: String = if self.nfkc {
+            string.nfkc().collect()
+        } else if self.nfc {
+            string.nfc().collect()
+        } else {
+            string.to_string()
+        };
+
+        if self.strip_accents {
+            out = out.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect();
+        }
+        if self.casefold {
+            out = casefold(&out);
+        } else if self.lowercase {
+            out = out.to_lowercase();
+        }
+        if self.remove_punctuation {
+            out.retain(|c| c.is_alphanumeric() || c.is_whitespace());
+        }
+        if self.collapse_whitespace {
+            out = out.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        out
+    }
+}
+
+/// Full Unicode case folding, e.g. `ß` and `ẞ` both fold to `ss`, computed as a
+/// lower-upper-lower round trip. This matches `CaseFolding.txt` except that the
+/// Turkish dotless `ı` folds to `i` as well.
+fn casefold(string: &str) -> String {
+    string.to_lowercase().to_uppercase().to_lowercase()
+}
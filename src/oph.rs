@@ -0,0 +1,93 @@
+use rustc_hash::FxHasher;
+use std::hash::{Hash, Hasher};
+
+use super::minihasher::MinHasher;
+use super::shingleset::ShingleSet;
+
+/// Random probes tried for an empty bin before falling back to rotation.
+const MAX_PROBES: u64 = 32;
+
+/// One-permutation hashing (Li et al., 2012) with optimal densification
+/// (Shrivastava, 2017).
+///
+/// A single hash of every shingle is split into `num_bins` bins by its high
+/// bits, keeping the minimum per bin, so the whole signature costs one pass
+/// over the shingles. Empty bins take the value of a non-empty bin chosen by
+/// a probe sequence that depends only on the bin, so that similar sets fill
+/// them consistently.
+///
+/// When sets have far fewer shingles than bins, probing until a filled bin is
+/// hit would cost `O(num_bins² / filled)`. After [`MAX_PROBES`] misses an empty
+/// bin takes the next filled bin instead (rotation densification, Shrivastava
+/// and Li, 2014), which keeps densification linear in `num_bins`.
+#[derive(Debug)]
+pub struct OnePermutationHasher {
+    seed: u64,
+    num_bins: usize,
+}
+
+impl OnePermutationHasher {
+    pub fn new(num_bins: usize, seed: u64) -> Self {
+        Self { seed, num_bins }
+    }
+
+    fn bin(&self, hash: u64) -> usize {
+        ((hash as u128 * self.num_bins as u128) >> 64) as usize
+    }
+
+    pub fn signature(&self, shingle_set: &ShingleSet) -> Vec<u64> {
+        let mut bins: Vec<Option<u64>> = vec![None; self.num_bins];
+        for shingle in &shingle_set.shingles {
+            let mut hasher = FxHasher::default();
+            self.seed.hash(&mut hasher);
+            shingle.hash(&mut hasher);
+            let hash = hasher.finish();
+
+            let bin = &mut bins[self.bin(hash)];
+            *bin = Some(bin.map_or(hash, |minimum| minimum.min(hash)));
+        }
+        if bins.iter().all(Option::is_none) {
+            return vec![u64::MAX; self.num_bins];
+        }
+
+        // The first filled bin at or after every bin, wrapping around.
+        let mut next_filled = vec![0; self.num_bins];
+        let mut next = bins
+            .iter()
+            .position(Option::is_some)
+            .expect("at least one bin is filled");
+        for bin_idx in (0..self.num_bins).rev() {
+            if bins[bin_idx].is_some() {
+                next = bin_idx;
+            }
+            next_filled[bin_idx] = next;
+        }
+
+        (0..self.num_bins)
+            .map(|bin_idx| {
+                (0..=MAX_PROBES)
+                    .find_map(|attempt| {
+                        if attempt == 0 {
+                            return bins[bin_idx];
+                        }
+                        let mut hasher = FxHasher::default();
+                        self.seed.hash(&mut hasher);
+                        bin_idx.hash(&mut hasher);
+                        attempt.hash(&mut hasher);
+                        bins[self.bin(hasher.finish())]
+                    })
+                    .or(bins[next_filled[bin_idx]])
+                    .expect("next_filled points to a filled bin")
+            })
+            .collect()
+    }
+
+    /// Band hashes of the signature, `band_size` bins per band, collapsed like
+    /// those of [`MinHasher::hash`].
+    pub fn band_hashes(&self, shingle_set: &ShingleSet, band_size: usize) -> Vec<u64> {
+        self.signature(shingle_set)
+            .chunks(band_size)
+            .map(|minima| MinHasher::hash_minima(minima.iter().copied()))
+            .collect()
+    }
+}
@@ -1,34 +1,68 @@
-use nohash_hasher::IntSet;
+use nohash_hasher::{IntMap, IntSet};
 use std::hash::{Hash, Hasher};
 
 use rustc_hash::FxHasher;
 
+/// How a string is split into the items that make up its shingles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShingleMode {
+    /// Character n-grams.
+    Chars,
+    /// Word n-grams, with words separated by whitespace and punctuation.
+    Words,
+}
+
 #[derive(Debug, Clone)]
 pub struct ShingleSet {
     pub shingles: IntSet<u32>,
+    // Only read by users of the library, which the Wasm example is not.
+    #[allow(dead_code)]
     pub shingle_len: usize,
+    #[allow(dead_code)]
     pub index: usize,
 }
 
 impl ShingleSet {
     pub fn new(string: &str, shingle_len: usize, index: usize, salt: Option<&str>) -> Self {
-        let mut out_set: IntSet<u32> = IntSet::default();
-
         let char_vec: Vec<char> = string.chars().collect();
 
-        for window in char_vec.windows(shingle_len) {
-            let mut hasher = FxHasher::default();
+        Self::from_items(&char_vec, shingle_len, index, salt)
+    }
 
-            if let Some(salt_str) = salt {
-                salt_str.hash(&mut hasher);
-            };
+    pub fn from_words(string: &str, shingle_len: usize, index: usize, salt: Option<&str>) -> Self {
+        let word_vec: Vec<&str> = string
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
 
-            window.hash(&mut hasher);
+        Self::from_items(&word_vec, shingle_len, index, salt)
+    }
 
-            let result: u32 = hasher.finish() as u32;
+    /// A set with one shingle per token, hashed like a single-word shingle.
+    pub fn from_tokens(tokens: &[&str], index: usize, salt: Option<&str>) -> Self {
+        Self::from_items(tokens, 1, index, salt)
+    }
 
-            out_set.insert(result);
+    pub fn with_mode(
+        string: &str,
+        shingle_len: usize,
+        index: usize,
+        salt: Option<&str>,
+        mode: ShingleMode,
+    ) -> Self {
+        match mode {
+            ShingleMode::Chars => Self::new(string, shingle_len, index, salt),
+            ShingleMode::Words => Self::from_words(string, shingle_len, index, salt),
         }
+    }
+
+    fn from_items<T: Hash>(
+        items: &[T],
+        shingle_len: usize,
+        index: usize,
+        salt: Option<&str>,
+    ) -> Self {
+        let out_set: IntSet<u32> = Self::hash_windows(items, shingle_len, salt).collect();
 
         Self {
             shingles: out_set,
@@ -37,6 +71,46 @@ impl ShingleSet {
         }
     }
 
+    fn hash_windows<'a, T: Hash>(
+        items: &'a [T],
+        shingle_len: usize,
+        salt: Option<&'a str>,
+    ) -> impl Iterator<Item = u32> + 'a {
+        items.windows(shingle_len).map(move |window| {
+            let mut hasher = FxHasher::default();
+
+            if let Some(salt_str) = salt {
+                salt_str.hash(&mut hasher);
+            };
+
+            window.hash(&mut hasher);
+
+            hasher.finish() as u32
+        })
+    }
+
+    /// The distinct character shingles of `string`, each with the number of
+    /// times it occurs.
+    pub fn weighted(string: &str, shingle_len: usize) -> Vec<(u32, f64)> {
+        let char_vec: Vec<char> = string.chars().collect();
+        let mut counts: IntMap<u32, f64> = IntMap::default();
+        for shingle in Self::hash_windows(&char_vec, shingle_len, None) {
+            *counts.entry(shingle).or_default() += 1.0;
+        }
+        counts.into_iter().collect()
+    }
+
+    /// The shingle of a single token, as in [`ShingleSet::from_tokens`].
+    pub fn token_shingle(token: &str) -> u32 {
+        let mut shingles = Self::hash_windows(std::slice::from_ref(&token), 1, None);
+        shingles.next().expect("a token is a shingle of length 1")
+    }
+
+    /// Adds the shingles of `other` to this set.
+    pub fn extend(&mut self, other: &Self) {
+        self.shingles.extend(&other.shingles);
+    }
+
     #[inline]
     pub fn jaccard_similarity(&self, b: &Self) -> f64 {
         if self.shingles.is_empty() | b.shingles.is_empty() {
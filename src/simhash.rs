@@ -0,0 +1,56 @@
+use rustc_hash::FxHasher;
+use std::hash::{Hash, Hasher};
+
+use super::minihasher::mix;
+
+/// Checks that a fingerprint of `bits` bits fits in a `UBIGINT`.
+pub fn validate_bits(bits: usize) -> Result<u32, String> {
+    if (1..=64).contains(&bits) {
+        Ok(bits as u32)
+    } else {
+        Err(format!("bits must be between 1 and 64, got {}", bits))
+    }
+}
+
+/// SimHash (Charikar, 2002) of `(shingle, weight)` pairs: every bit of the
+/// fingerprint is set when the weights of the shingles whose hash has that bit
+/// set outweigh those of the shingles whose hash does not. Two fingerprints
+/// differ in each bit with probability `θ / π`, where `θ` is the angle between
+/// the weight vectors.
+pub fn fingerprint(shingles: impl IntoIterator<Item = (u32, f64)>, bits: u32, seed: u64) -> u64 {
+    let mut sums = vec![0.0; bits as usize];
+    for (shingle, weight) in shingles {
+        let mut hasher = FxHasher::default();
+        seed.hash(&mut hasher);
+        shingle.hash(&mut hasher);
+        let hash = mix(hasher.finish());
+
+        for (bit, sum) in sums.iter_mut().enumerate() {
+            if hash >> bit & 1 == 1 {
+                *sum += weight;
+            } else {
+                *sum -= weight;
+            }
+        }
+    }
+    sums.iter()
+        .enumerate()
+        .filter(|(_, &sum)| sum > 0.0)
+        .fold(0, |fingerprint, (bit, _)| fingerprint | 1 << bit)
+}
+
+/// Splits the lowest `bits` bits of `fingerprint` into `band_count` runs of
+/// consecutive bits, the first `bits % band_count` of them one bit longer. By
+/// the pigeonhole principle, fingerprints within Hamming distance
+/// `band_count - 1` of each other agree on at least one band.
+pub fn bands(fingerprint: u64, bits: u32, band_count: u32) -> Vec<u64> {
+    let mut offset = 0;
+    (0..band_count)
+        .map(|band_idx| {
+            let width = bits / band_count + u32::from(band_idx < bits % band_count);
+            let band = (fingerprint >> offset) & ((1u128 << width) - 1) as u64;
+            offset += width;
+            band
+        })
+        .collect()
+}
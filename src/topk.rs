@@ -0,0 +1,188 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::error::Error;
+
+use duckdb::core::FlatVector;
+use duckdb::ffi;
+use duckdb::ffi::duckdb_string_t;
+use duckdb::types::DuckString;
+
+use super::aggregate::{
+    check_same_family, finalize_struct_lists, state_mut, struct_list_type, validate_count,
+    Aggregate, StructRow,
+};
+use super::shingleset::ShingleSet;
+use super::{validate_seed, HasherCache};
+
+/// The parameters of a search, which must be the same for every row of a group.
+#[derive(Clone, PartialEq)]
+struct TopKParams {
+    query: String,
+    k: usize,
+    ngram_width: usize,
+    num_perm: usize,
+    seed: u64,
+}
+
+/// A row and its similarity to the query; greater is more similar, with ties
+/// going to the smaller id.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    id: i64,
+    similarity: f64,
+}
+
+impl Eq for Neighbor {}
+
+impl Ord for Neighbor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then(other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Neighbor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl StructRow for Neighbor {
+    const FIELDS: &'static [(&'static str, ffi::DUCKDB_TYPE)] = &[
+        ("id", ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT),
+        ("similarity", ffi::DUCKDB_TYPE_DUCKDB_TYPE_DOUBLE),
+    ];
+
+    fn write(&self, fields: &mut [FlatVector], idx: usize) {
+        fields[0].as_mut_slice::<i64>()[idx] = self.id;
+        fields[1].as_mut_slice::<f64>()[idx] = self.similarity;
+    }
+}
+
+/// The `k` most similar rows of a group seen so far, in a min-heap so the
+/// least similar of them is the one replaced.
+pub struct TopKState {
+    params: TopKParams,
+    query: ShingleSet,
+    neighbors: BinaryHeap<Reverse<Neighbor>>,
+}
+
+impl TopKState {
+    fn push(&mut self, neighbor: Neighbor) {
+        self.neighbors.push(Reverse(neighbor));
+        if self.neighbors.len() > self.params.k {
+            self.neighbors.pop();
+        }
+    }
+
+    fn sorted(&self) -> Vec<Neighbor> {
+        let mut neighbors: Vec<Neighbor> = self.neighbors.iter().map(|&Reverse(n)| n).collect();
+        neighbors.sort_unstable_by(|a, b| b.cmp(a));
+        neighbors
+    }
+}
+
+/// `minhash_topk(id, text, query, k, ngram_width, num_perm, seed)`: the `k`
+/// rows of a group most similar to `query`, most similar first. Every row is
+/// ranked by its exact Jaccard similarity with the query in a single pass, so
+/// `num_perm` and `seed` are validated like those of `minhash` but do not
+/// change the result.
+pub struct MinHashTopK;
+
+impl Aggregate for MinHashTopK {
+    type State = TopKState;
+    const NAME: &'static str = "minhash_topk";
+
+    unsafe fn parameters() -> Vec<ffi::duckdb_logical_type> {
+        [
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+            ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT,
+        ]
+        .into_iter()
+        .map(|parameter| ffi::duckdb_create_logical_type(parameter))
+        .collect()
+    }
+
+    unsafe fn return_type() -> ffi::duckdb_logical_type {
+        struct_list_type::<Neighbor>()
+    }
+
+    unsafe fn update(
+        _: &HasherCache,
+        input: ffi::duckdb_data_chunk,
+        states: &[ffi::duckdb_aggregate_state],
+    ) -> Result<(), Box<dyn Error>> {
+        let len = states.len();
+        let columns: Vec<FlatVector> = (0..7)
+            .map(|col_idx| FlatVector::from(ffi::duckdb_data_chunk_get_vector(input, col_idx)))
+            .collect();
+        let ids = columns[0].as_slice_with_len::<i64>(len);
+        let texts = columns[1].as_slice_with_len::<duckdb_string_t>(len);
+        let queries = columns[2].as_slice_with_len::<duckdb_string_t>(len);
+        let counts: Vec<&[i64]> = columns[3..]
+            .iter()
+            .map(|vector| vector.as_slice_with_len::<i64>(len))
+            .collect();
+
+        for row_idx in 0..len {
+            if columns[2..]
+                .iter()
+                .any(|vector| vector.row_is_null(row_idx as u64))
+            {
+                continue;
+            }
+            let row_params = TopKParams {
+                query: DuckString::new(&mut { queries[row_idx] })
+                    .as_str()
+                    .to_string(),
+                k: validate_count(counts[0][row_idx], "k")?,
+                ngram_width: validate_count(counts[1][row_idx], "ngram_width")?,
+                num_perm: validate_count(counts[2][row_idx], "num_perm")?,
+                seed: validate_seed(counts[3][row_idx])?,
+            };
+            let state = state_mut::<TopKState>(states[row_idx]).get_or_insert_with(|| {
+                Box::new(TopKState {
+                    query: ShingleSet::new(&row_params.query, row_params.ngram_width, 0, None),
+                    params: row_params.clone(),
+                    neighbors: BinaryHeap::new(),
+                })
+            });
+            check_same_family(&state.params, &row_params)?;
+
+            if columns[0].row_is_null(row_idx as u64) || columns[1].row_is_null(row_idx as u64) {
+                continue;
+            }
+            let text = DuckString::new(&mut { texts[row_idx] })
+                .as_str()
+                .to_string();
+            let shingle_set = ShingleSet::new(&text, row_params.ngram_width, row_idx, None);
+            let similarity = state.query.jaccard_similarity(&shingle_set);
+            // Rows sharing no shingle with the query are not neighbours at all.
+            if similarity > 0.0 {
+                state.push(Neighbor {
+                    id: ids[row_idx],
+                    similarity,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(target: &mut TopKState, source: TopKState) -> Result<(), Box<dyn Error>> {
+        check_same_family(&target.params, &source.params)?;
+        for Reverse(neighbor) in source.neighbors {
+            target.push(neighbor);
+        }
+        Ok(())
+    }
+
+    unsafe fn finalize(states: &[Option<&TopKState>], result: ffi::duckdb_vector, offset: usize) {
+        finalize_struct_lists(states, result, offset, TopKState::sorted);
+    }
+}
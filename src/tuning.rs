@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use duckdb::{
+    core::{DataChunkHandle, LogicalTypeHandle, LogicalTypeId},
+    vtab::{BindInfo, InitInfo, TableFunctionInfo, VTab},
+};
+
+use super::lsh;
+use super::{parse_parameter, validate_positive, validate_unit_interval};
+
+pub struct MinHashLshParamsBindData {
+    band_count: usize,
+    band_size: usize,
+    false_positive: f64,
+    false_negative: f64,
+}
+
+pub struct MinHashLshParamsInitData {
+    done: AtomicBool,
+}
+
+pub struct MinHashLshParams;
+
+impl VTab for MinHashLshParams {
+    type InitData = MinHashLshParamsInitData;
+    type BindData = MinHashLshParamsBindData;
+
+    fn bind(bind: &BindInfo) -> Result<Self::BindData, Box<dyn Error>> {
+        bind.add_result_column("band_count", LogicalTypeId::Bigint.into());
+        bind.add_result_column("band_size", LogicalTypeId::Bigint.into());
+        bind.add_result_column("false_positive", LogicalTypeId::Double.into());
+        bind.add_result_column("false_negative", LogicalTypeId::Double.into());
+
+        let threshold =
+            validate_unit_interval(parse_parameter(bind, 0, "threshold")?, "threshold")?;
+        let false_positive_weight = validate_unit_interval(
+            parse_parameter(bind, 1, "false_positive_weight")?,
+            "false_positive_weight",
+        )?;
+        let max_perm = validate_positive(parse_parameter(bind, 2, "max_perm")?, "max_perm")?;
+
+        let (band_count, band_size) =
+            lsh::optimal_params(threshold, false_positive_weight, max_perm);
+        Ok(MinHashLshParamsBindData {
+            band_count,
+            band_size,
+            false_positive: lsh::false_positive_probability(threshold, band_count, band_size),
+            false_negative: lsh::false_negative_probability(threshold, band_count, band_size),
+        })
+    }
+
+    fn init(_: &InitInfo) -> Result<Self::InitData, Box<dyn Error>> {
+        Ok(MinHashLshParamsInitData {
+            done: AtomicBool::new(false),
+        })
+    }
+
+    fn func(
+        func: &TableFunctionInfo<Self>,
+        output: &mut DataChunkHandle,
+    ) -> Result<(), Box<dyn Error>> {
+        if func.get_init_data().done.swap(true, Ordering::Relaxed) {
+            output.set_len(0);
+            return Ok(());
+        }
+        let bind_data = func.get_bind_data();
+        output.flat_vector(0).as_mut_slice::<i64>()[0] = bind_data.band_count as i64;
+        output.flat_vector(1).as_mut_slice::<i64>()[0] = bind_data.band_size as i64;
+        output.flat_vector(2).as_mut_slice::<f64>()[0] = bind_data.false_positive;
+        output.flat_vector(3).as_mut_slice::<f64>()[0] = bind_data.false_negative;
+        output.set_len(1);
+        Ok(())
+    }
+
+    fn parameters() -> Option<Vec<LogicalTypeHandle>> {
+        Some(vec![
+            LogicalTypeId::Double.into(),
+            LogicalTypeId::Double.into(),
+            LogicalTypeId::Bigint.into(),
+        ])
+    }
+}
+
+pub struct MinHashCollisionCurveBindData {
+    band_count: usize,
+    band_size: usize,
+    resolution: usize,
+}
+
+pub struct MinHashCollisionCurveInitData {
+    offset: AtomicUsize,
+}
+
+/// Emits `resolution + 1` evenly spaced points `(similarity, probability)` of
+/// the S-curve, from similarity 0 to 1.
+pub struct MinHashCollisionCurve;
+
+impl VTab for MinHashCollisionCurve {
+    type InitData = MinHashCollisionCurveInitData;
+    type BindData = MinHashCollisionCurveBindData;
+
+    fn bind(bind: &BindInfo) -> Result<Self::BindData, Box<dyn Error>> {
+        bind.add_result_column("similarity", LogicalTypeId::Double.into());
+        bind.add_result_column("probability", LogicalTypeId::Double.into());
+
+        Ok(MinHashCollisionCurveBindData {
+            band_count: validate_positive(parse_parameter(bind, 0, "band_count")?, "band_count")?,
+            band_size: validate_positive(parse_parameter(bind, 1, "band_size")?, "band_size")?,
+            resolution: validate_positive(parse_parameter(bind, 2, "resolution")?, "resolution")?,
+        })
+    }
+
+    fn init(_: &InitInfo) -> Result<Self::InitData, Box<dyn Error>> {
+        Ok(MinHashCollisionCurveInitData {
+            offset: AtomicUsize::new(0),
+        })
+    }
+
+    fn func(
+        func: &TableFunctionInfo<Self>,
+        output: &mut DataChunkHandle,
+    ) -> Result<(), Box<dyn Error>> {
+        let bind_data = func.get_bind_data();
+        let init_data = func.get_init_data();
+        let num_points = bind_data.resolution + 1;
+        let capacity = output.flat_vector(0).capacity();
+        let start = init_data
+            .offset
+            .fetch_add(capacity, Ordering::Relaxed)
+            .min(num_points);
+        let end = (start + capacity).min(num_points);
+
+        let mut similarities = output.flat_vector(0);
+        let mut probabilities = output.flat_vector(1);
+        let similarities = similarities.as_mut_slice_with_len::<f64>(end - start);
+        let probabilities = probabilities.as_mut_slice_with_len::<f64>(end - start);
+        for (i, point) in (start..end).enumerate() {
+            let similarity = point as f64 / bind_data.resolution as f64;
+            similarities[i] = similarity;
+            probabilities[i] =
+                lsh::collision_probability(similarity, bind_data.band_count, bind_data.band_size);
+        }
+        output.set_len(end - start);
+        Ok(())
+    }
+
+    fn parameters() -> Option<Vec<LogicalTypeHandle>> {
+        Some(vec![
+            LogicalTypeId::Bigint.into(),
+            LogicalTypeId::Bigint.into(),
+            LogicalTypeId::Bigint.into(),
+        ])
+    }
+}